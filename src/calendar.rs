@@ -0,0 +1,239 @@
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use chrono::{DateTime, Datelike, Days, Duration, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::map::{Time, Trip};
+
+pub type ServiceIndex = usize;
+
+pub const FULL_DAY: Time = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Service {
+    weekdays: [bool; 7],
+    start: NaiveDate,
+    end: NaiveDate,
+    added: BTreeSet<NaiveDate>,
+    removed: BTreeSet<NaiveDate>,
+}
+
+impl Service {
+    pub fn new(weekdays: [bool; 7], start: NaiveDate, end: NaiveDate) -> Self {
+        Self {
+            weekdays,
+            start,
+            end,
+            added: BTreeSet::new(),
+            removed: BTreeSet::new(),
+        }
+    }
+
+    pub fn weekdays(&self) -> [bool; 7] {
+        self.weekdays
+    }
+
+    pub fn start(&self) -> NaiveDate {
+        self.start
+    }
+
+    pub fn end(&self) -> NaiveDate {
+        self.end
+    }
+
+    pub fn added(&self) -> impl Iterator<Item = &NaiveDate> {
+        self.added.iter()
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &NaiveDate> {
+        self.removed.iter()
+    }
+
+    pub fn add(&mut self, date: NaiveDate) {
+        self.removed.remove(&date);
+        self.added.insert(date);
+    }
+
+    pub fn remove(&mut self, date: NaiveDate) {
+        self.added.remove(&date);
+        self.removed.insert(date);
+    }
+
+    pub fn runs(&self, date: NaiveDate) -> bool {
+        if self.removed.contains(&date) {
+            return false;
+        }
+        if self.added.contains(&date) {
+            return true;
+        }
+        let weekday = date.weekday().num_days_from_monday() as usize;
+        self.start <= date && date <= self.end && self.weekdays[weekday]
+    }
+}
+
+// GTFS counts times from noon minus 12h, which is not midnight on DST change days
+pub fn service_start(timezone: &Tz, date: NaiveDate) -> DateTime<Tz> {
+    let noon = date.and_hms_opt(12, 0, 0).unwrap();
+    // Should a time zone ever skip noon, it is taken as noon in UTC
+    let noon = timezone
+        .from_local_datetime(&noon)
+        .earliest()
+        .unwrap_or_else(|| timezone.from_utc_datetime(&noon));
+    noon - Duration::hours(12)
+}
+
+pub fn service_time(moment: &DateTime<Tz>) -> (NaiveDate, Time) {
+    let date = moment.date_naive();
+    let start = service_start(&moment.timezone(), date);
+    (date, (*moment - start).num_seconds())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Day {
+    running: Option<Rc<[bool]>>,
+    offset: Time,
+}
+
+impl Day {
+    pub fn new(services: &[Service], date: NaiveDate) -> Self {
+        let running = services.iter().map(|s| s.runs(date)).collect();
+        Self {
+            running: Some(running),
+            offset: 0,
+        }
+    }
+
+    // Trips of the previous day may run past midnight and the search may go on after it,
+    // days next to a DST change are an hour shorter or longer
+    pub fn around(services: &[Service], timezone: &Tz, date: NaiveDate) -> Rc<[Day]> {
+        let start = service_start(timezone, date);
+        let offset = |day| (service_start(timezone, day) - start).num_seconds();
+        let previous = date - Days::new(1);
+        let next = date + Days::new(1);
+        Rc::from([
+            Self::new(services, previous).with_offset(offset(previous)),
+            Self::new(services, date),
+            Self::new(services, next).with_offset(offset(next)),
+        ])
+    }
+
+    pub fn with_offset(mut self, offset: Time) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn offset(&self) -> Time {
+        self.offset
+    }
+
+    pub fn runs(&self, trip: &Trip) -> bool {
+        if trip.is_cancelled() {
+            return false;
+        }
+        match (&self.running, trip.service()) {
+            (Some(running), Some(service)) => running[service],
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod service {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    fn weekdays() -> Service {
+        let workdays = [true, true, true, true, true, false, false];
+        Service::new(workdays, date(1), date(31))
+    }
+
+    #[test]
+    fn runs_on_weekday() {
+        assert!(weekdays().runs(date(3)));
+    }
+
+    #[test]
+    fn not_runs_on_weekend() {
+        assert!(!weekdays().runs(date(6)));
+    }
+
+    #[test]
+    fn not_runs_out_of_range() {
+        let service = weekdays();
+        assert!(!service.runs(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()));
+    }
+
+    #[test]
+    fn removed_date() {
+        let mut service = weekdays();
+        service.remove(date(1));
+        assert!(!service.runs(date(1)));
+    }
+
+    #[test]
+    fn added_date() {
+        let mut service = weekdays();
+        service.add(date(7));
+        assert!(service.runs(date(7)));
+    }
+
+    #[test]
+    fn any_day() {
+        let day = Day::default();
+        assert!(day.runs(&Trip::new(1, vec![10, 20]).with_service(0)));
+    }
+
+    #[test]
+    fn days_around() {
+        let days = Day::around(&[weekdays()], &Tz::Europe__Berlin, date(5));
+        let offsets: Vec<_> = days.iter().map(|d| d.offset()).collect();
+        assert_eq!(vec![-FULL_DAY, 0, FULL_DAY], offsets);
+        let trip = Trip::new(1, vec![10, 20]).with_service(0);
+        let running: Vec<_> = days.iter().map(|d| d.runs(&trip)).collect();
+        assert_eq!(vec![true, true, false], running);
+    }
+
+    #[test]
+    fn service_day_seconds() {
+        let moment = Tz::Europe__Berlin
+            .with_ymd_and_hms(2024, 1, 10, 8, 30, 0)
+            .unwrap();
+        assert_eq!((date(10), 8 * 3600 + 1800), service_time(&moment));
+    }
+
+    #[test]
+    fn daylight_saving_change() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let berlin = Tz::Europe__Berlin;
+        let before = berlin.with_ymd_and_hms(2024, 3, 31, 1, 30, 0).unwrap();
+        assert_eq!((date, 9000), service_time(&before));
+        let after = berlin.with_ymd_and_hms(2024, 3, 31, 10, 0, 0).unwrap();
+        assert_eq!((date, 10 * 3600), service_time(&after));
+        let start = service_start(&berlin, date);
+        assert_eq!("2024-03-30 23:00:00 CET", start.to_string());
+    }
+
+    #[test]
+    fn days_around_daylight_saving_change() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let days = Day::around(&[], &Tz::Europe__Berlin, date);
+        let offsets: Vec<_> = days.iter().map(|d| d.offset()).collect();
+        assert_eq!(vec![-FULL_DAY + 3600, 0, FULL_DAY], offsets);
+        let date = NaiveDate::from_ymd_opt(2024, 10, 27).unwrap();
+        let days = Day::around(&[], &Tz::Europe__Berlin, date);
+        let offsets: Vec<_> = days.iter().map(|d| d.offset()).collect();
+        assert_eq!(vec![-FULL_DAY - 3600, 0, FULL_DAY], offsets);
+    }
+
+    #[test]
+    fn trip_without_service() {
+        let day = Day::new(&[weekdays()], date(6));
+        assert!(day.runs(&Trip::new(1, vec![10, 20])));
+        assert!(!day.runs(&Trip::new(1, vec![10, 20]).with_service(0)));
+    }
+}
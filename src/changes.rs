@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::map::{PlatformIndex, Time};
+
+type Pairs = BTreeMap<(PlatformIndex, PlatformIndex), Time>;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeTimes {
+    global: Time,
+    platforms: BTreeMap<PlatformIndex, Time>,
+    #[serde(serialize_with = "pairs_to_list", deserialize_with = "pairs_from_list")]
+    pairs: Pairs,
+}
+
+// Formats like JSON only allow strings as keys, so pairs are kept as a list
+fn pairs_to_list<S: Serializer>(pairs: &Pairs, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(pairs)
+}
+
+fn pairs_from_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pairs, D::Error> {
+    let list = Vec::<((PlatformIndex, PlatformIndex), Time)>::deserialize(deserializer)?;
+    Ok(list.into_iter().collect())
+}
+
+impl ChangeTimes {
+    pub fn new(global: Time) -> Self {
+        Self {
+            global,
+            ..Self::default()
+        }
+    }
+
+    pub fn global(&self) -> Time {
+        self.global
+    }
+
+    pub fn platforms(&self) -> impl Iterator<Item = (&PlatformIndex, &Time)> {
+        self.platforms.iter()
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = (&(PlatformIndex, PlatformIndex), &Time)> {
+        self.pairs.iter()
+    }
+
+    pub fn set_platform(&mut self, platform: PlatformIndex, time: Time) {
+        self.platforms.insert(platform, time);
+    }
+
+    pub fn set_pair(&mut self, from: PlatformIndex, to: PlatformIndex, time: Time) {
+        self.pairs.insert((from, to), time);
+    }
+
+    pub fn time(&self, from: PlatformIndex, to: PlatformIndex) -> Time {
+        if let Some(time) = self.pairs.get(&(from, to)) {
+            return *time;
+        }
+        match self.platforms.get(&to) {
+            Some(time) => *time,
+            None => self.global,
+        }
+    }
+}
+
+#[cfg(test)]
+mod change {
+    use super::*;
+
+    fn changes() -> ChangeTimes {
+        let mut changes = ChangeTimes::new(60);
+        changes.set_platform(1, 120);
+        changes.set_pair(0, 1, 300);
+        changes
+    }
+
+    #[test]
+    fn global() {
+        assert_eq!(60, changes().time(2, 2));
+        assert_eq!(60, changes().time(1, 0));
+    }
+
+    #[test]
+    fn platform() {
+        assert_eq!(120, changes().time(1, 1));
+        assert_eq!(120, changes().time(2, 1));
+    }
+
+    #[test]
+    fn pair() {
+        assert_eq!(300, changes().time(0, 1));
+    }
+}
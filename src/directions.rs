@@ -0,0 +1,168 @@
+use std::fmt;
+
+use crate::map::{PlatformIndex, PublicTransport, Time};
+use crate::path::{Part, Path};
+
+// Turn by turn directions for riders, such as
+// "08:17 board route 23 toward Central, ride 6 stops, alight 08:31 at Arbatskaya"
+pub struct Directions<'a> {
+    map: &'a PublicTransport,
+    path: &'a Path,
+}
+
+impl<'a> Directions<'a> {
+    pub fn new(map: &'a PublicTransport, path: &'a Path) -> Self {
+        Self { map, path }
+    }
+
+    // Local time when the date is known, otherwise the time of the service day
+    fn clock(&self, time: Time) -> String {
+        match self.path.at(time) {
+            Some(moment) => moment.format("%H:%M").to_string(),
+            None => format!("{:02}:{:02}", time / 3600 % 24, time % 3600 / 60),
+        }
+    }
+
+    fn place(&self, platform: Option<PlatformIndex>) -> String {
+        match platform {
+            Some(platform) => self
+                .map
+                .platform_name(platform)
+                .unwrap_or_else(|| format!("platform {}", platform)),
+            None => "destination".to_string(),
+        }
+    }
+
+    fn ride(&self, f: &mut fmt::Formatter<'_>, part: &Part) -> fmt::Result {
+        let info = part.info();
+        let name = match info.and_then(|i| i.name()) {
+            Some(name) => name.to_string(),
+            None => part.route().unwrap_or_default().to_string(),
+        };
+        write!(f, "{} board route {}", self.clock(part.departure()), name)?;
+        if let Some(headsign) = info.and_then(|i| i.headsign.as_ref()) {
+            write!(f, " toward {}", headsign)?;
+        }
+        let stops = part.points().len() - 1;
+        writeln!(
+            f,
+            ", ride {} {}, alight {} at {}",
+            stops,
+            plural(stops, "stop"),
+            self.clock(part.arrival()),
+            self.place(part.to())
+        )
+    }
+}
+
+// Durations are rounded up, so a short walk is never shown as no walk
+fn minutes(seconds: Time) -> Time {
+    (seconds + 59) / 60
+}
+
+fn plural(count: usize, word: &str) -> String {
+    match count {
+        1 => word.to_string(),
+        _ => format!("{}s", word),
+    }
+}
+
+impl fmt::Display for Directions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.path.parts {
+            if part.route().is_some() {
+                self.ride(f, part)?;
+            } else if part.walking() > 0 {
+                writeln!(
+                    f,
+                    "{} walk {} min to {}",
+                    self.clock(part.departure()),
+                    minutes(part.walking()),
+                    self.place(part.to())
+                )?;
+            }
+        }
+        let transfers = self.path.transfers();
+        write!(
+            f,
+            "Total {} min, {} {}, {} min walking",
+            minutes(self.path.arrival - self.path.departure),
+            transfers,
+            plural(transfers, "transfer"),
+            minutes(self.path.walking)
+        )
+    }
+}
+
+#[cfg(test)]
+mod text {
+    use super::*;
+
+    use geo_types::coord;
+
+    use crate::map::{Platform, Point, Route, RouteInfo, Trip};
+
+    fn map() -> PublicTransport {
+        PublicTransport::new(
+            vec![
+                Platform::new(Point::new(0.0, 1.0), vec![0]).with_name("Kievskaya".to_string()),
+                Platform::new(Point::new(0.0, 2.0), vec![0]),
+                Platform::new(Point::new(0.0, 3.0), vec![0]).with_name("Arbatskaya".to_string()),
+            ],
+            vec![Route::new(
+                false,
+                vec![0, 1, 2],
+                vec![Trip::new(1, vec![100, 200, 300])],
+            )],
+            vec![vec![]; 3],
+        )
+    }
+
+    fn path(info: Option<RouteInfo>) -> Path {
+        let first = Part::new(vec![coord! {x: 0.5, y: 0.}, coord! {x: 1., y: 0.}], None)
+            .with_platforms(None, Some(0))
+            .with_times(29_520, 29_760)
+            .with_walking(240);
+        let ride = Part::new(
+            vec![
+                coord! {x: 1., y: 0.},
+                coord! {x: 2., y: 0.},
+                coord! {x: 3., y: 0.},
+            ],
+            Some(0),
+        )
+        .with_platforms(Some(0), Some(2))
+        .with_times(30_000, 30_660)
+        .with_waiting(240);
+        let ride = match info {
+            Some(info) => ride.with_info(info),
+            None => ride,
+        };
+        let last = Part::new(vec![coord! {x: 3., y: 0.}, coord! {x: 3., y: 0.}], None)
+            .with_platforms(Some(2), None)
+            .with_times(30_660, 30_660);
+        Path::new(vec![first, ride, last], 29_520, 30_660).with_walking(240)
+    }
+
+    #[test]
+    fn legs_and_summary() {
+        let info = RouteInfo {
+            short_name: Some("23".to_string()),
+            headsign: Some("Central".to_string()),
+            ..RouteInfo::default()
+        };
+        let expected = "08:12 walk 4 min to Kievskaya\n\
+                        08:20 board route 23 toward Central, ride 2 stops, alight 08:31 at Arbatskaya\n\
+                        Total 19 min, 0 transfers, 4 min walking";
+        assert_eq!(
+            expected,
+            Directions::new(&map(), &path(Some(info))).to_string()
+        );
+    }
+
+    #[test]
+    fn unnamed_route() {
+        let text = Directions::new(&map(), &path(None)).to_string();
+        assert!(text.contains("08:20 board route 0, ride 2 stops"));
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::ops::Range;
+
+use serde::Deserialize;
+
+use crate::gtfs::parse_time;
+use crate::map::{PlatformIndex, PublicTransport, Time};
+
+#[derive(Debug)]
+pub enum Error {
+    Csv(csv::Error),
+    Invalid(usize, String),
+    Unknown(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Csv(e) => write!(f, "{}", e),
+            Error::Invalid(line, message) => write!(f, "line {}: {}", line, message),
+            Error::Unknown(what) => write!(f, "unknown {}", what),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Error::Csv(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Disruption {
+    Cancel(String),
+    Close(String, Option<Range<Time>>),
+}
+
+#[derive(Deserialize)]
+struct DisruptionRecord {
+    kind: String,
+    id: String,
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    to: Option<String>,
+}
+
+// A CSV file with kind,id,from,to where kind is "trip" or "platform"
+pub fn read_disruptions(filename: &str) -> Result<Vec<Disruption>, Error> {
+    let mut csv = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(filename)?;
+    let mut disruptions = vec![];
+    for (number, record) in csv.deserialize().enumerate() {
+        let record: DisruptionRecord = record?;
+        let line = number + 2;
+        let time = |time: Option<String>| match time.filter(|t| !t.is_empty()) {
+            Some(time) => match parse_time(&time) {
+                Some(time) => Ok(Some(time)),
+                None => Err(Error::Invalid(line, format!("bad time {}", time))),
+            },
+            None => Ok(None),
+        };
+        let disruption = match record.kind.as_str() {
+            "trip" => Disruption::Cancel(record.id),
+            "platform" => match (time(record.from)?, time(record.to)?) {
+                (Some(from), Some(to)) => Disruption::Close(record.id, Some(from..to)),
+                (None, None) => Disruption::Close(record.id, None),
+                _ => return Err(Error::Invalid(line, "closure needs both ends".to_string())),
+            },
+            kind => return Err(Error::Invalid(line, format!("unknown kind {}", kind))),
+        };
+        disruptions.push(disruption);
+    }
+    Ok(disruptions)
+}
+
+impl PublicTransport {
+    // Ids are those of the feed, or bare indices for maps without them
+    pub fn disrupt(&mut self, disruptions: &[Disruption]) -> Result<(), Error> {
+        for disruption in disruptions {
+            match disruption {
+                Disruption::Cancel(id) => {
+                    let trip = self.trip_ids.get(id).copied().or_else(|| id.parse().ok());
+                    if !trip.is_some_and(|t| self.cancel_trip(t, true)) {
+                        return Err(Error::Unknown(format!("trip {}", id)));
+                    }
+                }
+                Disruption::Close(id, interval) => {
+                    let Some(platform) = self.platform_by_id(id) else {
+                        return Err(Error::Unknown(format!("platform {}", id)));
+                    };
+                    match interval {
+                        Some(interval) => self.platforms[platform].close(interval.clone()),
+                        None => self.platforms[platform].close_fully(),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn platform_by_id(&self, id: &str) -> Option<PlatformIndex> {
+        let platform = self.stop_ids.get(id).copied().or_else(|| id.parse().ok())?;
+        (platform < self.platforms.len()).then_some(platform)
+    }
+}
+
+#[cfg(test)]
+mod disruption {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::map::{Platform, Point, Route, Trip};
+
+    fn map() -> PublicTransport {
+        PublicTransport::new(
+            vec![
+                Platform::new(Point::new(0.0, 0.0), vec![0]),
+                Platform::new(Point::new(0.0, 1.0), vec![0]),
+            ],
+            vec![Route::new(
+                false,
+                vec![0, 1],
+                vec![Trip::new(1, vec![10, 20]), Trip::new(2, vec![30, 40])],
+            )],
+            vec![vec![]; 2],
+        )
+        .with_trip_ids(HashMap::from([("T1".to_string(), 1)]))
+        .with_stop_ids(HashMap::from([("A".to_string(), 0)]))
+    }
+
+    fn temp(content: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("tranet-disruptions-{}.csv", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn read() {
+        let filename =
+            temp("kind,id,from,to\ntrip,T1,,\nplatform,A,08:00:00,09:00:00\nplatform,1,,\n");
+        let disruptions = read_disruptions(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        let expected = vec![
+            Disruption::Cancel("T1".to_string()),
+            Disruption::Close("A".to_string(), Some(8 * 3600..9 * 3600)),
+            Disruption::Close("1".to_string(), None),
+        ];
+        assert_eq!(expected, disruptions);
+    }
+
+    #[test]
+    fn apply() {
+        let mut map = map();
+        let disruptions = vec![
+            Disruption::Cancel("T1".to_string()),
+            Disruption::Cancel("2".to_string()),
+            Disruption::Close("A".to_string(), Some(0..100)),
+            Disruption::Close("1".to_string(), None),
+        ];
+        map.disrupt(&disruptions).unwrap();
+        assert!(map.routes[0].trips().iter().all(|t| t.is_cancelled()));
+        assert!(!map.is_open(0, 50));
+        assert!(map.is_open(0, 100));
+        assert!(!map.is_open(1, 100));
+    }
+
+    #[test]
+    fn unknown() {
+        let mut map = map();
+        let cancel = map.disrupt(&[Disruption::Cancel("T7".to_string())]);
+        assert!(matches!(cancel, Err(Error::Unknown(_))));
+        let close = map.disrupt(&[Disruption::Close("9".to_string(), None)]);
+        assert!(matches!(close, Err(Error::Unknown(_))));
+    }
+}
@@ -0,0 +1,1041 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::calendar::{Service, ServiceIndex};
+use crate::changes::ChangeTimes;
+use crate::map::{
+    Frequency, Mode, Passage, Platform, PlatformIndex, Point, PublicTransport, Route, RouteInfo,
+    Station, Time, Trip,
+};
+use crate::platforms::{duration, WalkingProfile};
+use crate::spatial::{self, Grid};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Zip(ZipError),
+    Csv(String, csv::Error),
+    Missing(String),
+    Invalid(String, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Zip(e) => write!(f, "{}", e),
+            Error::Csv(file, e) => write!(f, "{}: {}", file, e),
+            Error::Missing(file) => write!(f, "{}: file is missing", file),
+            Error::Invalid(file, message) => write!(f, "{}: {}", file, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(e: ZipError) -> Self {
+        Error::Zip(e)
+    }
+}
+
+trait Feed {
+    fn open(&mut self, name: &str) -> Result<Option<Box<dyn Read + '_>>, Error>;
+}
+
+struct Directory {
+    path: PathBuf,
+}
+
+impl Feed for Directory {
+    fn open(&mut self, name: &str) -> Result<Option<Box<dyn Read + '_>>, Error> {
+        let path = self.path.join(name);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(File::open(path)?)))
+    }
+}
+
+struct Archive {
+    zip: ZipArchive<File>,
+}
+
+impl Feed for Archive {
+    fn open(&mut self, name: &str) -> Result<Option<Box<dyn Read + '_>>, Error> {
+        match self.zip.by_name(name) {
+            Ok(file) => Ok(Some(Box::new(file))),
+            Err(ZipError::FileNotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AgencyRecord {
+    #[serde(default)]
+    agency_id: Option<String>,
+    #[serde(default)]
+    agency_name: Option<String>,
+    agency_timezone: String,
+}
+
+#[derive(Deserialize)]
+struct StopRecord {
+    stop_id: String,
+    #[serde(default)]
+    stop_name: Option<String>,
+    #[serde(default)]
+    platform_code: Option<String>,
+    #[serde(default)]
+    stop_lat: Option<f64>,
+    #[serde(default)]
+    stop_lon: Option<f64>,
+    #[serde(default)]
+    location_type: Option<u8>,
+    #[serde(default)]
+    parent_station: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RouteRecord {
+    route_id: String,
+    #[serde(default)]
+    agency_id: Option<String>,
+    #[serde(default)]
+    route_short_name: Option<String>,
+    #[serde(default)]
+    route_long_name: Option<String>,
+    #[serde(default)]
+    route_type: Option<u16>,
+    #[serde(default)]
+    route_color: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TripRecord {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+    #[serde(default)]
+    trip_headsign: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CalendarRecord {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    start_date: String,
+    end_date: String,
+}
+
+#[derive(Deserialize)]
+struct CalendarDateRecord {
+    service_id: String,
+    date: String,
+    exception_type: u8,
+}
+
+#[derive(Deserialize)]
+struct StopTimeRecord {
+    trip_id: String,
+    #[serde(default)]
+    arrival_time: Option<String>,
+    #[serde(default)]
+    departure_time: Option<String>,
+    stop_id: String,
+    stop_sequence: u32,
+}
+
+#[derive(Deserialize)]
+struct FrequencyRecord {
+    trip_id: String,
+    start_time: String,
+    end_time: String,
+    headway_secs: Time,
+}
+
+#[derive(Deserialize)]
+struct TransferRecord {
+    from_stop_id: String,
+    to_stop_id: String,
+    #[serde(default)]
+    transfer_type: Option<u8>,
+    #[serde(default)]
+    min_transfer_time: Option<Time>,
+}
+
+struct StopTime {
+    sequence: u32,
+    platform: PlatformIndex,
+    time: Option<Time>,
+}
+
+#[derive(Default)]
+struct Stops {
+    index: HashMap<String, PlatformIndex>,
+    points: Vec<Point>,
+    names: Vec<(Option<String>, Option<String>)>,
+    stations: HashMap<String, Vec<PlatformIndex>>,
+    station_names: HashMap<String, String>,
+}
+
+impl Stops {
+    fn resolve(&self, id: &str) -> Vec<PlatformIndex> {
+        match self.index.get(id) {
+            Some(platform) => vec![*platform],
+            None => self.stations.get(id).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Services {
+    index: HashMap<String, ServiceIndex>,
+    services: Vec<Service>,
+}
+
+impl Services {
+    fn entry(&mut self, id: String) -> &mut Service {
+        let next = self.services.len();
+        let index = *self.index.entry(id).or_insert(next);
+        if index == next {
+            self.services.push(Service::default());
+        }
+        &mut self.services[index]
+    }
+}
+
+type Pattern = (String, Vec<PlatformIndex>);
+// Times, service, GTFS trip id and stop sequences of each trip
+type Schedule = Vec<(Vec<Time>, Option<ServiceIndex>, String, Vec<u32>)>;
+
+pub fn read_gtfs(path: &str) -> Result<PublicTransport, Error> {
+    let path = Path::new(path);
+    match path.is_dir() {
+        true => load(&mut Directory {
+            path: path.to_path_buf(),
+        }),
+        false => load(&mut Archive {
+            zip: ZipArchive::new(File::open(path)?)?,
+        }),
+    }
+}
+
+fn load(feed: &mut dyn Feed) -> Result<PublicTransport, Error> {
+    let (timezone, agencies) = read_agencies(feed)?;
+    let stops = read_stops(feed)?;
+    let routes = read_routes(feed, &agencies)?;
+    let services = read_services(feed)?;
+    let trips = read_trips(feed, &routes, &services)?;
+    let times = read_stop_times(feed, &stops, &trips)?;
+    let frequencies = read_frequencies(feed, &trips)?;
+    let (passages, changes) = read_transfers(feed, &stops)?;
+    Ok(build(
+        stops,
+        services,
+        &routes,
+        &trips,
+        times,
+        frequencies,
+        passages,
+    )
+    .with_changes(changes)
+    .with_timezone(timezone))
+}
+
+fn each<T: DeserializeOwned>(
+    feed: &mut dyn Feed,
+    name: &str,
+    required: bool,
+    mut f: impl FnMut(T) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let reader = match feed.open(name)? {
+        Some(reader) => reader,
+        None if required => return Err(Error::Missing(name.to_string())),
+        None => return Ok(()),
+    };
+    let mut csv = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    for record in csv.deserialize() {
+        f(record.map_err(|e| Error::Csv(name.to_string(), e))?)?;
+    }
+    Ok(())
+}
+
+// All agencies of a feed share one time zone
+// Agency names by id, a feed with a single agency may leave the id out
+type Agencies = HashMap<Option<String>, String>;
+
+fn read_agencies(feed: &mut dyn Feed) -> Result<(Tz, Agencies), Error> {
+    let mut timezone = None;
+    let mut agencies = Agencies::new();
+    each(feed, "agency.txt", false, |agency: AgencyRecord| {
+        if let Some(name) = agency.agency_name {
+            agencies.insert(agency.agency_id, name);
+        }
+        if timezone.is_none() {
+            let parsed = agency.agency_timezone.parse::<Tz>().map_err(|_| {
+                Error::Invalid(
+                    "agency.txt".to_string(),
+                    format!("unknown time zone {}", agency.agency_timezone),
+                )
+            })?;
+            timezone = Some(parsed);
+        }
+        Ok(())
+    })?;
+    Ok((timezone.unwrap_or(Tz::UTC), agencies))
+}
+
+fn agency(agencies: &Agencies, id: Option<String>) -> Option<String> {
+    match (agencies.get(&id), agencies.len()) {
+        (Some(name), _) => Some(name.clone()),
+        (None, 1) if id.is_none() => agencies.values().next().cloned(),
+        _ => None,
+    }
+}
+
+// Basic and extended GTFS route types
+fn mode(route_type: u16) -> Option<Mode> {
+    match route_type {
+        0 | 5 | 900..=906 => Some(Mode::Tram),
+        1 | 12 | 400..=405 => Some(Mode::Metro),
+        2 | 7 | 100..=117 => Some(Mode::Rail),
+        3 | 11 | 200..=209 | 700..=716 | 800 => Some(Mode::Bus),
+        4 | 1000 | 1200 => Some(Mode::Ferry),
+        _ => None,
+    }
+}
+
+fn read_stops(feed: &mut dyn Feed) -> Result<Stops, Error> {
+    let mut stops = Stops::default();
+    let mut parents = vec![];
+    each(feed, "stops.txt", true, |stop: StopRecord| {
+        if let (Some(1), Some(name)) = (stop.location_type, &stop.stop_name) {
+            stops.station_names.insert(stop.stop_id, name.clone());
+            return Ok(());
+        }
+        if stop.location_type.unwrap_or(0) != 0 {
+            return Ok(());
+        }
+        let (Some(lat), Some(lon)) = (stop.stop_lat, stop.stop_lon) else {
+            return Err(Error::Invalid(
+                "stops.txt".to_string(),
+                format!("stop {} has no coordinates", stop.stop_id),
+            ));
+        };
+        let platform = stops.points.len();
+        stops.index.insert(stop.stop_id, platform);
+        stops.points.push(Point::new(lat, lon));
+        stops.names.push((stop.stop_name, stop.platform_code));
+        if let Some(parent) = stop.parent_station.filter(|p| !p.is_empty()) {
+            parents.push((parent, platform));
+        }
+        Ok(())
+    })?;
+    for (parent, platform) in parents {
+        stops.stations.entry(parent).or_default().push(platform);
+    }
+    Ok(stops)
+}
+
+fn read_routes(
+    feed: &mut dyn Feed,
+    agencies: &Agencies,
+) -> Result<HashMap<String, RouteInfo>, Error> {
+    let mut routes = HashMap::new();
+    each(feed, "routes.txt", true, |route: RouteRecord| {
+        let info = RouteInfo {
+            short_name: route.route_short_name,
+            long_name: route.route_long_name,
+            mode: route.route_type.and_then(mode),
+            color: route.route_color,
+            agency: agency(agencies, route.agency_id),
+            headsign: None,
+        };
+        routes.insert(route.route_id, info);
+        Ok(())
+    })?;
+    Ok(routes)
+}
+
+fn parse_date(file: &str, date: &str) -> Result<NaiveDate, Error> {
+    NaiveDate::parse_from_str(date, "%Y%m%d")
+        .map_err(|_| Error::Invalid(file.to_string(), format!("bad date {}", date)))
+}
+
+fn read_services(feed: &mut dyn Feed) -> Result<Services, Error> {
+    let mut services = Services::default();
+    each(feed, "calendar.txt", false, |record: CalendarRecord| {
+        let weekdays = [
+            record.monday,
+            record.tuesday,
+            record.wednesday,
+            record.thursday,
+            record.friday,
+            record.saturday,
+            record.sunday,
+        ]
+        .map(|day| day == 1);
+        let start = parse_date("calendar.txt", &record.start_date)?;
+        let end = parse_date("calendar.txt", &record.end_date)?;
+        *services.entry(record.service_id) = Service::new(weekdays, start, end);
+        Ok(())
+    })?;
+    each(
+        feed,
+        "calendar_dates.txt",
+        false,
+        |record: CalendarDateRecord| {
+            let date = parse_date("calendar_dates.txt", &record.date)?;
+            let service = services.entry(record.service_id);
+            match record.exception_type {
+                1 => service.add(date),
+                2 => service.remove(date),
+                _ => {
+                    return Err(Error::Invalid(
+                        "calendar_dates.txt".to_string(),
+                        format!("bad exception type {}", record.exception_type),
+                    ))
+                }
+            }
+            Ok(())
+        },
+    )?;
+    Ok(services)
+}
+
+fn read_trips(
+    feed: &mut dyn Feed,
+    routes: &HashMap<String, RouteInfo>,
+    services: &Services,
+) -> Result<HashMap<String, TripRecord>, Error> {
+    let mut trips = HashMap::new();
+    each(feed, "trips.txt", true, |trip: TripRecord| {
+        if !routes.contains_key(&trip.route_id) {
+            return Err(Error::Invalid(
+                "trips.txt".to_string(),
+                format!(
+                    "trip {} refers to unknown route {}",
+                    trip.trip_id, trip.route_id
+                ),
+            ));
+        }
+        if !services.services.is_empty() && !services.index.contains_key(&trip.service_id) {
+            return Err(Error::Invalid(
+                "trips.txt".to_string(),
+                format!(
+                    "trip {} refers to unknown service {}",
+                    trip.trip_id, trip.service_id
+                ),
+            ));
+        }
+        trips.insert(trip.trip_id.clone(), trip);
+        Ok(())
+    })?;
+    Ok(trips)
+}
+
+fn read_stop_times(
+    feed: &mut dyn Feed,
+    stops: &Stops,
+    trips: &HashMap<String, TripRecord>,
+) -> Result<BTreeMap<String, Vec<StopTime>>, Error> {
+    let name = "stop_times.txt";
+    let invalid = |message: String| Error::Invalid(name.to_string(), message);
+    let mut times: BTreeMap<String, Vec<StopTime>> = BTreeMap::new();
+    each(feed, name, true, |record: StopTimeRecord| {
+        if !trips.contains_key(&record.trip_id) {
+            return Err(invalid(format!("unknown trip {}", record.trip_id)));
+        }
+        let Some(platform) = stops.index.get(&record.stop_id) else {
+            return Err(invalid(format!("unknown stop {}", record.stop_id)));
+        };
+        let time = match record.departure_time.or(record.arrival_time) {
+            Some(time) if !time.is_empty() => {
+                Some(parse_time(&time).ok_or_else(|| invalid(format!("bad time {}", time)))?)
+            }
+            _ => None,
+        };
+        times.entry(record.trip_id).or_default().push(StopTime {
+            sequence: record.stop_sequence,
+            platform: *platform,
+            time,
+        });
+        Ok(())
+    })?;
+    for (trip, stops) in times.iter_mut() {
+        stops.sort_by_key(|s| s.sequence);
+        if stops.len() < 2 || stops[0].time.is_none() || stops[stops.len() - 1].time.is_none() {
+            return Err(invalid(format!("trip {} is not timed at both ends", trip)));
+        }
+        interpolate(stops);
+    }
+    Ok(times)
+}
+
+fn interpolate(stops: &mut [StopTime]) {
+    let mut previous = 0;
+    for next in 1..stops.len() {
+        if let Some(time) = stops[next].time {
+            let start = stops[previous].time.unwrap();
+            let steps = (next - previous) as Time;
+            for (step, stop) in stops[previous + 1..next].iter_mut().enumerate() {
+                stop.time = Some(start + (time - start) * (step + 1) as Time / steps);
+            }
+            previous = next;
+        }
+    }
+}
+
+fn read_frequencies(
+    feed: &mut dyn Feed,
+    trips: &HashMap<String, TripRecord>,
+) -> Result<HashMap<String, Vec<Frequency>>, Error> {
+    let name = "frequencies.txt";
+    let invalid = |message: String| Error::Invalid(name.to_string(), message);
+    let mut frequencies: HashMap<String, Vec<Frequency>> = HashMap::new();
+    each(feed, name, false, |record: FrequencyRecord| {
+        if !trips.contains_key(&record.trip_id) {
+            return Err(invalid(format!("unknown trip {}", record.trip_id)));
+        }
+        let time =
+            |time: &str| parse_time(time).ok_or_else(|| invalid(format!("bad time {}", time)));
+        let (start, end) = (time(&record.start_time)?, time(&record.end_time)?);
+        if record.headway_secs <= 0 || end <= start {
+            return Err(invalid(format!("bad frequency of trip {}", record.trip_id)));
+        }
+        frequencies
+            .entry(record.trip_id)
+            .or_default()
+            .push(Frequency::new(start, end, record.headway_secs));
+        Ok(())
+    })?;
+    Ok(frequencies)
+}
+
+type Transfers = (Vec<Vec<Passage>>, ChangeTimes);
+
+fn read_transfers(feed: &mut dyn Feed, stops: &Stops) -> Result<Transfers, Error> {
+    let mut passages = vec![vec![]; stops.points.len()];
+    let mut changes = ChangeTimes::default();
+    let points: Vec<_> = stops
+        .points
+        .iter()
+        .map(|p| geo_types::Point::new(p.lon, p.lat))
+        .collect();
+    let grid = Grid::from_points(&points);
+    let profile = WalkingProfile::default();
+    // Without a minimal time a transfer between stops takes as long as walking straight
+    let walk = |from: PlatformIndex, to: PlatformIndex| {
+        let distance = spatial::distance(&grid.project(&points[from]), &grid.project(&points[to]));
+        duration(distance, &profile)
+    };
+    each(feed, "transfers.txt", false, |transfer: TransferRecord| {
+        if transfer.transfer_type == Some(3) {
+            return Ok(());
+        }
+        let minimal = transfer.transfer_type == Some(2);
+        for from in stops.resolve(&transfer.from_stop_id) {
+            for to in stops.resolve(&transfer.to_stop_id) {
+                let time = match transfer.min_transfer_time {
+                    Some(time) => time,
+                    None if from != to => walk(from, to),
+                    None => 0,
+                };
+                if from != to {
+                    passages[from].push(Passage::new(to, time));
+                }
+                match (minimal, from == to) {
+                    (true, true) => changes.set_platform(to, time),
+                    (true, false) => changes.set_pair(from, to, time),
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    })?;
+    Ok((passages, changes))
+}
+
+fn build(
+    stops: Stops,
+    services: Services,
+    infos: &HashMap<String, RouteInfo>,
+    trips: &HashMap<String, TripRecord>,
+    times: BTreeMap<String, Vec<StopTime>>,
+    mut frequencies: HashMap<String, Vec<Frequency>>,
+    passages: Vec<Vec<Passage>>,
+) -> PublicTransport {
+    let mut patterns: BTreeMap<Pattern, Schedule> = BTreeMap::new();
+    for (name, stops) in times {
+        let trip = &trips[&name];
+        let service = services.index.get(&trip.service_id).copied();
+        let platforms = stops.iter().map(|s| s.platform).collect();
+        let sequences = stops.iter().map(|s| s.sequence).collect();
+        let stops = stops.iter().map(|s| s.time.unwrap()).collect();
+        patterns
+            .entry((trip.route_id.clone(), platforms))
+            .or_default()
+            .push((stops, service, name, sequences));
+    }
+    let mut platforms: Vec<Platform> = stops
+        .points
+        .into_iter()
+        .zip(stops.names)
+        .map(|(point, (name, code))| {
+            let mut platform = Platform::new(point, vec![]);
+            platform.name = name;
+            platform.code = code;
+            platform
+        })
+        .collect();
+    for (id, platform) in &stops.index {
+        platforms[*platform].id = Some(id.clone());
+    }
+    let mut stations: Vec<_> = stops
+        .stations
+        .into_iter()
+        .map(|(id, platforms)| Station {
+            name: stops.station_names.get(&id).cloned(),
+            id: Some(id),
+            platforms,
+        })
+        .collect();
+    stations.sort_by(|a, b| a.id.cmp(&b.id));
+    let stop_ids = stops.index;
+    let mut routes = vec![];
+    let mut trip_ids = HashMap::new();
+    let mut id = 0;
+    for ((route, mut pattern), schedule) in patterns {
+        // The pattern takes the headsign of its first trip that has one
+        let info = RouteInfo {
+            headsign: schedule
+                .iter()
+                .find_map(|(_, _, name, _)| trips[name].trip_headsign.clone()),
+            ..infos[&route].clone()
+        };
+        let circle = pattern.len() > 2 && pattern.first() == pattern.last();
+        if circle {
+            pattern.pop();
+        }
+        for schedule in split_overtaking(schedule, &frequencies) {
+            let index = routes.len();
+            for platform in &pattern {
+                let routes = &mut platforms[*platform].routes;
+                if !routes.contains(&index) {
+                    routes.push(index);
+                }
+            }
+            let trips = schedule
+                .into_iter()
+                .map(|(stops, service, name, sequences)| {
+                    id += 1;
+                    let frequencies = frequencies.remove(&name).unwrap_or_default();
+                    trip_ids.insert(name, id);
+                    let trip = Trip::new(id, stops)
+                        .with_frequencies(frequencies)
+                        .with_sequences(sequences);
+                    match service {
+                        Some(service) => trip.with_service(service),
+                        None => trip,
+                    }
+                })
+                .collect();
+            let route = Route::new(circle, pattern.clone(), trips).with_info(info.clone());
+            routes.push(route);
+        }
+    }
+    PublicTransport::new(platforms, routes, passages)
+        .with_services(services.services)
+        .with_stations(stations)
+        .with_trip_ids(trip_ids)
+        .with_stop_ids(stop_ids)
+}
+
+// RAPTOR needs the trips of a route in the same order at every stop, so a trip
+// overtaking another goes to a route of its own. Frequent trips are patterns of
+// offsets, not times, and stay with the first route.
+fn split_overtaking(
+    mut schedule: Schedule,
+    frequencies: &HashMap<String, Vec<Frequency>>,
+) -> Vec<Schedule> {
+    schedule.sort();
+    let mut routes: Vec<Schedule> = vec![vec![]];
+    for trip in schedule {
+        if frequencies.contains_key(&trip.2) {
+            routes[0].push(trip);
+            continue;
+        }
+        let keeps_order = |route: &&mut Schedule| {
+            let last = route.iter().rev().find(|t| !frequencies.contains_key(&t.2));
+            last.is_none_or(|(stops, ..)| {
+                stops
+                    .iter()
+                    .zip(&trip.0)
+                    .all(|(before, after)| before <= after)
+            })
+        };
+        match routes.iter_mut().find(keeps_order) {
+            Some(route) => route.push(trip),
+            None => routes.push(vec![trip]),
+        }
+    }
+    routes
+}
+
+pub(crate) fn parse_time(time: &str) -> Option<Time> {
+    let mut parts = time.split(':');
+    let hours: Time = parts.next()?.parse().ok()?;
+    let minutes: Time = parts.next()?.parse().ok()?;
+    let seconds: Time = parts.next()?.parse().ok()?;
+    match parts.next() {
+        Some(_) => None,
+        None => Some(hours * 3600 + minutes * 60 + seconds),
+    }
+}
+
+#[cfg(test)]
+mod feed {
+    use super::*;
+
+    use crate::calendar::Day;
+
+    struct Memory {
+        files: HashMap<&'static str, &'static str>,
+    }
+
+    impl Feed for Memory {
+        fn open(&mut self, name: &str) -> Result<Option<Box<dyn Read + '_>>, Error> {
+            Ok(self
+                .files
+                .get(name)
+                .map(|content| Box::new(content.as_bytes()) as Box<dyn Read>))
+        }
+    }
+
+    const AGENCY: &str = "agency_id,agency_name,agency_url,agency_timezone
+A,Agency,https://example.com,Europe/Moscow
+";
+
+    const STOPS: &str = "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station
+S,Station,55.0,37.0,1,
+A,A,55.1,37.1,0,S
+B,B,55.2,37.2,0,S
+C,C,55.3,37.3,,
+";
+
+    const ROUTES: &str = "route_id,route_short_name,route_long_name,route_type,route_color
+R1,1,,3,
+R2,2,Ring,0,00FF00
+";
+
+    const TRIPS: &str = "route_id,service_id,trip_id,trip_headsign
+R1,daily,T1,C
+R1,weekend,T2,
+R1,daily,T3,
+R2,daily,T4,
+";
+
+    const CALENDAR: &str =
+        "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date
+daily,1,1,1,1,1,1,1,20240101,20241231
+weekend,0,0,0,0,0,1,1,20240101,20241231
+";
+
+    const CALENDAR_DATES: &str = "service_id,date,exception_type
+weekend,20240101,1
+daily,20240101,2
+holiday,20240508,1
+";
+
+    const STOP_TIMES: &str = "trip_id,arrival_time,departure_time,stop_id,stop_sequence
+T1,08:00:00,08:00:00,A,1
+T1,08:05:00,08:05:00,B,2
+T1,08:10:00,08:10:00,C,3
+T2,07:00:00,07:00:00,A,1
+T2,,,B,2
+T2,07:10:00,07:10:00,C,3
+T3,25:00:00,25:00:00,A,1
+T3,25:10:00,25:10:00,C,2
+T4,09:00:00,09:00:00,A,1
+T4,09:05:00,09:05:00,B,2
+T4,09:10:00,09:10:00,C,3
+T4,09:15:00,09:15:00,A,4
+";
+
+    const TRANSFERS: &str = "from_stop_id,to_stop_id,transfer_type,min_transfer_time
+C,S,2,120
+A,B,3,
+B,B,2,90
+";
+
+    fn feed() -> Memory {
+        Memory {
+            files: HashMap::from([
+                ("agency.txt", AGENCY),
+                ("stops.txt", STOPS),
+                ("routes.txt", ROUTES),
+                ("trips.txt", TRIPS),
+                ("stop_times.txt", STOP_TIMES),
+                ("transfers.txt", TRANSFERS),
+                ("calendar.txt", CALENDAR),
+                ("calendar_dates.txt", CALENDAR_DATES),
+            ]),
+        }
+    }
+
+    #[test]
+    fn time() {
+        assert_eq!(Some(8 * 3600 + 5 * 60 + 7), parse_time("08:05:07"));
+        assert_eq!(Some(25 * 3600), parse_time("25:00:00"));
+        assert_eq!(Some(7 * 3600), parse_time("7:00:00"));
+        assert_eq!(None, parse_time("7:00"));
+    }
+
+    #[test]
+    fn platforms_without_stations() {
+        let map = load(&mut feed()).unwrap();
+        assert_eq!(3, map.platforms.len());
+        assert_eq!(55.1, map.platforms[0].point.lat);
+    }
+
+    #[test]
+    fn routes_by_pattern() {
+        let map = load(&mut feed()).unwrap();
+        assert_eq!(3, map.routes.len());
+        assert_eq!(vec![0, 1, 2], map.platforms[0].routes);
+        assert_eq!(vec![0, 2], map.platforms[1].routes);
+    }
+
+    #[test]
+    fn sorted_trips() {
+        let map = load(&mut feed()).unwrap();
+        let trip = map.routes[0]
+            .try_catch(7 * 3600, 0, None, &Day::default())
+            .unwrap();
+        assert_eq!(7 * 3600 + 300, trip.stop(1, false));
+    }
+
+    #[test]
+    fn services() {
+        let map = load(&mut feed()).unwrap();
+        assert_eq!(3, map.services.len());
+        let holiday = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert!(!map.services[0].runs(holiday));
+        assert!(map.services[1].runs(holiday));
+        assert!(map.services[2].runs(NaiveDate::from_ymd_opt(2024, 5, 8).unwrap()));
+    }
+
+    #[test]
+    fn trips_by_date() {
+        let map = load(&mut feed()).unwrap();
+        let monday = NaiveDate::from_ymd_opt(2024, 1, 8).unwrap();
+        let day = Day::new(&map.services, monday);
+        let trip = map.routes[0].try_catch(7 * 3600, 0, None, &day).unwrap();
+        assert_eq!(8 * 3600, trip.first());
+    }
+
+    #[test]
+    fn unknown_service() {
+        let mut feed = feed();
+        feed.files
+            .insert("trips.txt", "route_id,service_id,trip_id\nR1,never,T1\n");
+        assert!(matches!(load(&mut feed), Err(Error::Invalid(_, _))));
+    }
+
+    #[test]
+    fn circle_route() {
+        let map = load(&mut feed()).unwrap();
+        let route = &map.routes[2];
+        assert!(route.circle);
+        assert_eq!(0, route.platform(3));
+    }
+
+    #[test]
+    fn transfers_to_station() {
+        let map = load(&mut feed()).unwrap();
+        let passages = &map.passages[2];
+        assert_eq!(2, passages.len());
+        assert_eq!(0, passages[0].to);
+        assert_eq!(120, passages[0].time);
+        assert!(map.passages[0].is_empty());
+    }
+
+    #[test]
+    fn transfer_without_time() {
+        let mut feed = feed();
+        feed.files.insert(
+            "transfers.txt",
+            "from_stop_id,to_stop_id,transfer_type,min_transfer_time\nA,C,0,\nB,B,1,\n",
+        );
+        let map = load(&mut feed).unwrap();
+        let passage = &map.passages[0][0];
+        assert_eq!(2, passage.to);
+        assert!(passage.time > 18000 && passage.time < 19000);
+        assert!(map.passages[1].is_empty());
+    }
+
+    #[test]
+    fn change_times() {
+        let map = load(&mut feed()).unwrap();
+        assert_eq!(90, map.changes.time(1, 1));
+        assert_eq!(120, map.changes.time(2, 0));
+        assert_eq!(0, map.changes.time(0, 0));
+    }
+
+    #[test]
+    fn timezone() {
+        let map = load(&mut feed()).unwrap();
+        assert_eq!(Tz::Europe__Moscow, map.timezone);
+        let mut feed = feed();
+        feed.files.remove("agency.txt");
+        assert_eq!(Tz::UTC, load(&mut feed).unwrap().timezone);
+        feed.files.insert(
+            "agency.txt",
+            "agency_name,agency_timezone\nA,Mars/Olympus\n",
+        );
+        assert!(matches!(load(&mut feed), Err(Error::Invalid(_, _))));
+    }
+
+    #[test]
+    fn stable_ids() {
+        let map = load(&mut feed()).unwrap();
+        let id = map.trip_ids["T2"];
+        let trip = map.routes[0].trips().iter().find(|t| t.id() == id).unwrap();
+        assert_eq!(7 * 3600, *trip.first());
+        assert_eq!(&[1, 2, 3], trip.sequences());
+        assert_eq!(2, map.stop_ids["C"]);
+    }
+
+    #[test]
+    fn route_info() {
+        let map = load(&mut feed()).unwrap();
+        let info = &map.routes[0].info;
+        assert_eq!(Some("1"), info.name());
+        assert_eq!(Some(Mode::Bus), info.mode);
+        assert_eq!(Some("Agency"), info.agency.as_deref());
+        assert_eq!(Some("C"), info.headsign.as_deref());
+        let info = &map.routes[2].info;
+        assert_eq!("Tram 2", info.to_string());
+        assert_eq!(Some("Ring"), info.long_name.as_deref());
+        assert_eq!(Some("00FF00"), info.color.as_deref());
+        assert_eq!(None, map.routes[1].info.headsign);
+    }
+
+    #[test]
+    fn stations() {
+        let map = load(&mut feed()).unwrap();
+        assert_eq!(1, map.stations.len());
+        assert_eq!(Some("Station"), map.stations[0].name.as_deref());
+        assert_eq!(vec![0, 1], map.stations[0].platforms);
+        assert_eq!(Some(0), map.platforms[1].station);
+        assert_eq!(Some("B"), map.platforms[1].id.as_deref());
+        assert_eq!(vec![0, 1], map.find_by_name("station"));
+        assert_eq!(vec![2], map.find_by_name("C"));
+    }
+
+    #[test]
+    fn overtaking_trips() {
+        let mut feed = feed();
+        feed.files.insert(
+            "trips.txt",
+            "route_id,service_id,trip_id\nR1,daily,L1\nR1,daily,E1\nR1,daily,L2\n",
+        );
+        feed.files.insert(
+            "stop_times.txt",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence
+L1,08:00:00,08:00:00,A,1
+L1,08:30:00,08:30:00,C,2
+E1,08:05:00,08:05:00,A,1
+E1,08:20:00,08:20:00,C,2
+L2,08:10:00,08:10:00,A,1
+L2,08:40:00,08:40:00,C,2
+",
+        );
+        let map = load(&mut feed).unwrap();
+        assert_eq!(2, map.routes.len());
+        assert_eq!(map.routes[0].platforms(), map.routes[1].platforms());
+        assert_eq!(vec![0, 1], map.platforms[0].routes);
+        let firsts: Vec<_> = map.routes[0].trips().iter().map(|t| *t.first()).collect();
+        assert_eq!(vec![8 * 3600, 8 * 3600 + 600], firsts);
+        let express = map.routes[1]
+            .try_catch(8 * 3600 + 1, 0, None, &Day::default())
+            .unwrap();
+        assert_eq!(8 * 3600 + 1200, express.stop(1, false));
+    }
+
+    #[test]
+    fn frequencies() {
+        let mut feed = feed();
+        feed.files.insert(
+            "frequencies.txt",
+            "trip_id,start_time,end_time,headway_secs\nT3,06:00:00,07:00:00,600\n",
+        );
+        let map = load(&mut feed).unwrap();
+        let route = &map.routes[1];
+        assert!(route.trips().is_empty());
+        assert_eq!(
+            &[Frequency::new(6 * 3600, 7 * 3600, 600)],
+            route.templates()[0].frequencies()
+        );
+        let trip = route
+            .try_catch(6 * 3600 + 1, 0, None, &Day::default())
+            .unwrap();
+        assert_eq!(6 * 3600 + 600, trip.first());
+        assert_eq!(6 * 3600 + 1200, trip.last());
+        let trip = route.try_catch(7 * 3600 - 599, 0, None, &Day::default());
+        assert!(trip.is_none());
+    }
+
+    #[test]
+    fn bad_frequency() {
+        let mut feed = feed();
+        feed.files.insert(
+            "frequencies.txt",
+            "trip_id,start_time,end_time,headway_secs\nT3,07:00:00,06:00:00,600\n",
+        );
+        assert!(matches!(load(&mut feed), Err(Error::Invalid(_, _))));
+    }
+
+    #[test]
+    fn missing_stop_times() {
+        let mut feed = feed();
+        feed.files.remove("stop_times.txt");
+        assert!(matches!(load(&mut feed), Err(Error::Missing(_))));
+    }
+
+    #[test]
+    fn unknown_stop() {
+        let mut feed = feed();
+        feed.files.insert(
+            "stop_times.txt",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\nT1,08:00:00,08:00:00,X,1\n",
+        );
+        assert!(matches!(load(&mut feed), Err(Error::Invalid(_, _))));
+    }
+}
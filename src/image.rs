@@ -0,0 +1,988 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::Range;
+use std::sync::Arc;
+
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
+use memmap2::Mmap;
+
+use crate::calendar::Service;
+use crate::changes::ChangeTimes;
+use crate::map::{
+    Frequency, Mode, Passage, Platform, Point, PublicTransport, Route, RouteInfo, Station, Times,
+    Trip,
+};
+
+const MAGIC: &[u8; 8] = b"TRANETMP";
+// 2 added change times, 3 the time zone, 4 GTFS ids, 5 frequencies, 6 route infos
+// 7 platform and station names and 8 stop sequences
+const VERSION: u32 = 8;
+const ALIGN: usize = 8;
+const HEADER: usize = 16;
+const ENTRY: usize = 24;
+
+const POINTS: u32 = 1;
+const PLATFORM_ROUTES_INDEX: u32 = 2;
+const PLATFORM_ROUTES: u32 = 3;
+const ROUTE_CIRCLES: u32 = 4;
+const ROUTE_PLATFORMS_INDEX: u32 = 5;
+const ROUTE_PLATFORMS: u32 = 6;
+const ROUTE_TRIPS_INDEX: u32 = 7;
+const TRIP_IDS: u32 = 8;
+const TRIP_SERVICES: u32 = 9;
+const TRIP_STOPS_INDEX: u32 = 10;
+const TRIP_STOPS: u32 = 11;
+const PASSAGES_INDEX: u32 = 12;
+const PASSAGE_TARGETS: u32 = 13;
+const PASSAGE_TIMES: u32 = 14;
+const SERVICE_WEEKDAYS: u32 = 15;
+const SERVICE_RANGES: u32 = 16;
+const SERVICE_ADDED_INDEX: u32 = 17;
+const SERVICE_ADDED: u32 = 18;
+const SERVICE_REMOVED_INDEX: u32 = 19;
+const SERVICE_REMOVED: u32 = 20;
+const CHANGE_GLOBAL: u32 = 21;
+const CHANGE_PLATFORMS: u32 = 22;
+const CHANGE_PAIRS: u32 = 23;
+const TIMEZONE: u32 = 24;
+const TRIP_KEYS_INDEX: u32 = 25;
+const TRIP_KEYS: u32 = 26;
+const TRIP_KEY_IDS: u32 = 27;
+const STOP_KEYS_INDEX: u32 = 28;
+const STOP_KEYS: u32 = 29;
+const STOP_KEY_PLATFORMS: u32 = 30;
+const TRIP_FREQUENCIES_INDEX: u32 = 31;
+const TRIP_FREQUENCIES: u32 = 32;
+const ROUTE_MODES: u32 = 33;
+const ROUTE_TEXTS_INDEX: u32 = 34;
+const ROUTE_TEXTS: u32 = 35;
+const PLATFORM_TEXTS_INDEX: u32 = 36;
+const PLATFORM_TEXTS: u32 = 37;
+const STATION_TEXTS_INDEX: u32 = 38;
+const STATION_TEXTS: u32 = 39;
+const STATION_PLATFORMS_INDEX: u32 = 40;
+const STATION_PLATFORMS: u32 = 41;
+const TRIP_SEQUENCES_INDEX: u32 = 42;
+const TRIP_SEQUENCES: u32 = 43;
+
+// Texts are stored in a fixed order for each item, an empty one stands for none
+const ROUTE_TEXT_COUNT: usize = 5;
+const PLATFORM_TEXT_COUNT: usize = 3;
+const STATION_TEXT_COUNT: usize = 2;
+const NO_MODE: u8 = u8::MAX;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Format(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+fn invalid<T>(message: String) -> Result<T, Error> {
+    Err(Error::Format(message))
+}
+
+/// # Safety
+/// Implementors must be valid for any bit pattern and have no padding.
+unsafe trait Plain: Copy {
+    fn put(self, out: &mut Vec<u8>);
+}
+
+macro_rules! plain {
+    ($($t:ty),*) => {
+        $(unsafe impl Plain for $t {
+            fn put(self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+        })*
+    };
+}
+
+plain!(u8, u32, i32, u64, i64, f64);
+
+#[derive(Default)]
+struct Sections {
+    entries: Vec<(u32, Vec<u8>)>,
+}
+
+impl Sections {
+    fn push<T: Plain>(&mut self, tag: u32, values: impl IntoIterator<Item = T>) {
+        let mut bytes = vec![];
+        for value in values {
+            value.put(&mut bytes);
+        }
+        self.entries.push((tag, bytes));
+    }
+
+    fn push_jagged<T: Plain, I: IntoIterator<Item = T>>(
+        &mut self,
+        index: u32,
+        tag: u32,
+        items: impl IntoIterator<Item = I>,
+    ) {
+        let mut offsets = vec![0u64];
+        let mut values = vec![];
+        for item in items {
+            values.extend(item);
+            offsets.push(values.len() as u64);
+        }
+        self.push(index, offsets);
+        self.push(tag, values);
+    }
+
+    fn push_texts<'a>(
+        &mut self,
+        tags: [u32; 2],
+        texts: impl IntoIterator<Item = &'a Option<String>>,
+    ) {
+        let texts = texts.into_iter();
+        self.push_jagged(
+            tags[0],
+            tags[1],
+            texts.map(|t| t.as_deref().unwrap_or_default().bytes()),
+        );
+    }
+
+    fn push_keys<'a>(&mut self, tags: [u32; 3], keys: impl IntoIterator<Item = (&'a String, i64)>) {
+        let mut keys: Vec<_> = keys.into_iter().collect();
+        keys.sort();
+        self.push_jagged(tags[0], tags[1], keys.iter().map(|(k, _)| k.bytes()));
+        self.push(tags[2], keys.iter().map(|(_, v)| *v));
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut header = vec![];
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        let mut offset = align(HEADER + ENTRY * self.entries.len());
+        for (tag, bytes) in &self.entries {
+            header.extend_from_slice(&tag.to_le_bytes());
+            header.extend_from_slice(&0u32.to_le_bytes());
+            header.extend_from_slice(&(offset as u64).to_le_bytes());
+            header.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            offset = align(offset + bytes.len());
+        }
+        header.resize(align(header.len()), 0);
+        out.write_all(&header)?;
+        for (_, bytes) in &self.entries {
+            out.write_all(bytes)?;
+            out.write_all(&vec![0; align(bytes.len()) - bytes.len()])?;
+        }
+        Ok(())
+    }
+}
+
+fn align(size: usize) -> usize {
+    size.div_ceil(ALIGN) * ALIGN
+}
+
+fn days(date: NaiveDate) -> i32 {
+    date.num_days_from_ce()
+}
+
+pub fn write_image(map: &PublicTransport, filename: &str) -> Result<(), Error> {
+    let mut sections = Sections::default();
+    sections.push(
+        POINTS,
+        map.platforms
+            .iter()
+            .flat_map(|p| [p.point.lat, p.point.lon]),
+    );
+    sections.push_jagged(
+        PLATFORM_ROUTES_INDEX,
+        PLATFORM_ROUTES,
+        map.platforms
+            .iter()
+            .map(|p| p.routes.iter().map(|r| *r as u64)),
+    );
+    sections.push_texts(
+        [PLATFORM_TEXTS_INDEX, PLATFORM_TEXTS],
+        map.platforms.iter().flat_map(|p| [&p.name, &p.id, &p.code]),
+    );
+    sections.push_texts(
+        [STATION_TEXTS_INDEX, STATION_TEXTS],
+        map.stations.iter().flat_map(|s| [&s.id, &s.name]),
+    );
+    sections.push_jagged(
+        STATION_PLATFORMS_INDEX,
+        STATION_PLATFORMS,
+        map.stations
+            .iter()
+            .map(|s| s.platforms.iter().map(|p| *p as u64)),
+    );
+    sections.push(ROUTE_CIRCLES, map.routes.iter().map(|r| r.circle as u8));
+    sections.push(
+        ROUTE_MODES,
+        map.routes
+            .iter()
+            .map(|r| r.info.mode.map_or(NO_MODE, |m| m as u8)),
+    );
+    sections.push_texts(
+        [ROUTE_TEXTS_INDEX, ROUTE_TEXTS],
+        map.routes.iter().flat_map(|r| {
+            let info = &r.info;
+            [
+                &info.short_name,
+                &info.long_name,
+                &info.color,
+                &info.agency,
+                &info.headsign,
+            ]
+        }),
+    );
+    sections.push_jagged(
+        ROUTE_PLATFORMS_INDEX,
+        ROUTE_PLATFORMS,
+        map.routes
+            .iter()
+            .map(|r| r.platforms().iter().map(|p| *p as u64)),
+    );
+    let mut offset = 0;
+    let mut trips_index = vec![0u64];
+    for route in &map.routes {
+        offset += (route.trips().len() + route.templates().len()) as u64;
+        trips_index.push(offset);
+    }
+    sections.push(ROUTE_TRIPS_INDEX, trips_index);
+    let trips = || {
+        map.routes
+            .iter()
+            .flat_map(|r| r.trips().iter().chain(r.templates()))
+    };
+    sections.push(TRIP_IDS, trips().map(|t| t.id()));
+    sections.push(
+        TRIP_SERVICES,
+        trips().map(|t| t.service().map_or(-1, |s| s as i64)),
+    );
+    sections.push_jagged(
+        TRIP_STOPS_INDEX,
+        TRIP_STOPS,
+        trips().map(|t| t.stops().iter().copied()),
+    );
+    sections.push_jagged(
+        TRIP_FREQUENCIES_INDEX,
+        TRIP_FREQUENCIES,
+        trips().map(|t| {
+            t.frequencies()
+                .iter()
+                .flat_map(|f| [f.start, f.end, f.headway])
+        }),
+    );
+    sections.push_jagged(
+        TRIP_SEQUENCES_INDEX,
+        TRIP_SEQUENCES,
+        trips().map(|t| t.sequences().iter().copied()),
+    );
+    sections.push_jagged(
+        PASSAGES_INDEX,
+        PASSAGE_TARGETS,
+        map.passages.iter().map(|p| p.iter().map(|p| p.to as u64)),
+    );
+    sections.push(
+        PASSAGE_TIMES,
+        map.passages.iter().flat_map(|p| p.iter().map(|p| p.time)),
+    );
+    sections.push(
+        SERVICE_WEEKDAYS,
+        map.services.iter().map(|s| {
+            s.weekdays()
+                .iter()
+                .enumerate()
+                .fold(0u8, |mask, (i, runs)| mask | ((*runs as u8) << i))
+        }),
+    );
+    sections.push(
+        SERVICE_RANGES,
+        map.services
+            .iter()
+            .flat_map(|s| [days(s.start()), days(s.end())]),
+    );
+    sections.push_jagged(
+        SERVICE_ADDED_INDEX,
+        SERVICE_ADDED,
+        map.services.iter().map(|s| s.added().map(|d| days(*d))),
+    );
+    sections.push_jagged(
+        SERVICE_REMOVED_INDEX,
+        SERVICE_REMOVED,
+        map.services.iter().map(|s| s.removed().map(|d| days(*d))),
+    );
+    let changes = &map.changes;
+    sections.push(CHANGE_GLOBAL, [changes.global()]);
+    sections.push(
+        CHANGE_PLATFORMS,
+        changes.platforms().flat_map(|(p, t)| [*p as i64, *t]),
+    );
+    sections.push(
+        CHANGE_PAIRS,
+        changes
+            .pairs()
+            .flat_map(|((from, to), t)| [*from as i64, *to as i64, *t]),
+    );
+    sections.push(TIMEZONE, map.timezone.name().bytes());
+    sections.push_keys(
+        [TRIP_KEYS_INDEX, TRIP_KEYS, TRIP_KEY_IDS],
+        map.trip_ids.iter().map(|(k, v)| (k, *v as i64)),
+    );
+    sections.push_keys(
+        [STOP_KEYS_INDEX, STOP_KEYS, STOP_KEY_PLATFORMS],
+        map.stop_ids.iter().map(|(k, v)| (k, *v as i64)),
+    );
+    let mut out = BufWriter::new(File::create(filename)?);
+    sections.write(&mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn is_image(filename: &str) -> bool {
+    let mut magic = [0; 8];
+    match File::open(filename) {
+        Ok(mut file) => file.read_exact(&mut magic).is_ok() && &magic == MAGIC,
+        Err(_) => false,
+    }
+}
+
+struct Jagged<'a, T> {
+    index: &'a [u64],
+    values: &'a [T],
+}
+
+impl<'a, T> Jagged<'a, T> {
+    fn len(&self) -> usize {
+        self.index.len() - 1
+    }
+
+    fn get(&self, i: usize) -> &'a [T] {
+        &self.values[self.index[i] as usize..self.index[i + 1] as usize]
+    }
+}
+
+// Trips of a loaded map keep their stop times in the mapping, which outlives the image
+pub struct Image {
+    mmap: Arc<Mmap>,
+    sections: HashMap<u32, Range<usize>>,
+}
+
+impl Image {
+    pub fn open(filename: &str) -> Result<Self, Error> {
+        if cfg!(target_endian = "big") {
+            return invalid("map images are little-endian only".to_string());
+        }
+        let file = File::open(filename)?;
+        // The file is expected not to be modified while it is mapped
+        let mmap = unsafe { Mmap::map(&file)? };
+        let sections = read_header(&mmap)?;
+        Ok(Self {
+            mmap: Arc::new(mmap),
+            sections,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    // Values of a jagged section item, left in place
+    fn mapped(&self, tag: u32, jagged: &Jagged<'_, i64>, i: usize) -> Times {
+        let start = self.sections[&tag].start;
+        let size = std::mem::size_of::<i64>();
+        let (from, to) = (jagged.index[i] as usize, jagged.index[i + 1] as usize);
+        // The section was checked to be aligned and to hold the whole item
+        unsafe { Times::mapped(self.mmap.clone(), start + from * size..start + to * size) }
+    }
+
+    fn optional<T: Plain>(&self, tag: u32) -> Result<Option<&[T]>, Error> {
+        let Some(range) = self.sections.get(&tag) else {
+            return Ok(None);
+        };
+        let bytes = &self.bytes()[range.clone()];
+        let (prefix, values, suffix) = unsafe { bytes.align_to::<T>() };
+        if !prefix.is_empty() || !suffix.is_empty() {
+            return invalid(format!("section {} is misaligned", tag));
+        }
+        Ok(Some(values))
+    }
+
+    fn section<T: Plain>(&self, tag: u32) -> Result<&[T], Error> {
+        match self.optional(tag)? {
+            Some(values) => Ok(values),
+            None => invalid(format!("section {} is missing", tag)),
+        }
+    }
+
+    fn jagged<T: Plain>(&self, index: u32, tag: u32, size: usize) -> Result<Jagged<'_, T>, Error> {
+        let index = self.section::<u64>(index)?;
+        let values = self.section::<T>(tag)?;
+        let sorted = index.windows(2).all(|w| w[0] <= w[1]);
+        if index.len() != size + 1
+            || index[0] != 0
+            || !sorted
+            || index[size] as usize > values.len()
+        {
+            return invalid(format!("section {} has bad offsets", tag));
+        }
+        Ok(Jagged { index, values })
+    }
+
+    pub fn platforms(&self) -> Result<Vec<Platform>, Error> {
+        let points = self.section::<f64>(POINTS)?;
+        let size = points.len() / 2;
+        let routes = self.jagged::<u64>(PLATFORM_ROUTES_INDEX, PLATFORM_ROUTES, size)?;
+        Ok((0..size)
+            .map(|i| {
+                let point = Point::new(points[2 * i], points[2 * i + 1]);
+                Platform::new(point, routes.get(i).iter().map(|r| *r as usize).collect())
+            })
+            .collect())
+    }
+
+    pub fn routes(&self) -> Result<Vec<Route>, Error> {
+        let circles = self.section::<u8>(ROUTE_CIRCLES)?;
+        let size = circles.len();
+        let platforms = self.jagged::<u64>(ROUTE_PLATFORMS_INDEX, ROUTE_PLATFORMS, size)?;
+        let trips = self.section::<u64>(ROUTE_TRIPS_INDEX)?;
+        let ids = self.section::<i32>(TRIP_IDS)?;
+        let services = self.section::<i64>(TRIP_SERVICES)?;
+        let stops = self.jagged::<i64>(TRIP_STOPS_INDEX, TRIP_STOPS, ids.len())?;
+        // Images written before frequencies have none
+        let frequencies = match self.optional::<u64>(TRIP_FREQUENCIES_INDEX)? {
+            Some(_) => {
+                Some(self.jagged::<i64>(TRIP_FREQUENCIES_INDEX, TRIP_FREQUENCIES, ids.len())?)
+            }
+            None => None,
+        };
+        // Images written before stop sequences have none
+        let sequences = match self.optional::<u64>(TRIP_SEQUENCES_INDEX)? {
+            Some(_) => Some(self.jagged::<u32>(TRIP_SEQUENCES_INDEX, TRIP_SEQUENCES, ids.len())?),
+            None => None,
+        };
+        if frequencies
+            .as_ref()
+            .is_some_and(|f| f.index.iter().any(|i| i % 3 != 0))
+        {
+            return invalid("frequency sections do not agree".to_string());
+        }
+        if trips.len() != size + 1
+            || trips[size] as usize != ids.len()
+            || services.len() != ids.len()
+        {
+            return invalid("trip sections do not agree".to_string());
+        }
+        let mut routes = vec![];
+        for (r, circle) in circles.iter().enumerate() {
+            let (from, to) = (trips[r] as usize, trips[r + 1] as usize);
+            if from > to || to > ids.len() {
+                return invalid(format!("route {} has bad trips", r));
+            }
+            let trips = (from..to)
+                .map(|t| {
+                    let frequencies = frequencies.as_ref().map_or(vec![], |f| {
+                        f.get(t)
+                            .chunks_exact(3)
+                            .map(|f| Frequency::new(f[0], f[1], f[2]))
+                            .collect()
+                    });
+                    let sequences = sequences.as_ref().map_or(vec![], |s| s.get(t).to_vec());
+                    let times = self.mapped(TRIP_STOPS, &stops, t);
+                    let trip = Trip::from_times(ids[t], times)
+                        .with_frequencies(frequencies)
+                        .with_sequences(sequences);
+                    match services[t] {
+                        s if s >= 0 => trip.with_service(s as usize),
+                        _ => trip,
+                    }
+                })
+                .collect();
+            let platforms = platforms.get(r).iter().map(|p| *p as usize).collect();
+            routes.push(Route::new(*circle != 0, platforms, trips));
+        }
+        self.route_infos(&mut routes)?;
+        Ok(routes)
+    }
+
+    // Images written before route infos leave them empty
+    fn route_infos(&self, routes: &mut [Route]) -> Result<(), Error> {
+        let Some(modes) = self.optional::<u8>(ROUTE_MODES)? else {
+            return Ok(());
+        };
+        let tags = [ROUTE_TEXTS_INDEX, ROUTE_TEXTS];
+        let texts = self.texts(tags, routes.len() * ROUTE_TEXT_COUNT)?;
+        if modes.len() != routes.len() {
+            return invalid("route sections do not agree".to_string());
+        }
+        let chunks = texts.chunks_exact(ROUTE_TEXT_COUNT);
+        for ((route, mode), texts) in routes.iter_mut().zip(modes).zip(chunks) {
+            let [short_name, long_name, color, agency, headsign] =
+                texts.to_vec().try_into().unwrap();
+            route.info = RouteInfo {
+                short_name,
+                long_name,
+                mode: Mode::ALL.get(*mode as usize).copied(),
+                color,
+                agency,
+                headsign,
+            };
+        }
+        Ok(())
+    }
+
+    fn texts(&self, tags: [u32; 2], size: usize) -> Result<Vec<Option<String>>, Error> {
+        let texts = self.jagged::<u8>(tags[0], tags[1], size)?;
+        (0..size)
+            .map(|i| match std::str::from_utf8(texts.get(i)) {
+                Ok("") => Ok(None),
+                Ok(text) => Ok(Some(text.to_string())),
+                Err(_) => invalid(format!("section {} has a bad text", tags[1])),
+            })
+            .collect()
+    }
+
+    // Images written before platform names have neither names nor stations
+    fn names(&self, platforms: &mut [Platform]) -> Result<Vec<Station>, Error> {
+        if self.optional::<u64>(PLATFORM_TEXTS_INDEX)?.is_none() {
+            return Ok(vec![]);
+        }
+        let tags = [PLATFORM_TEXTS_INDEX, PLATFORM_TEXTS];
+        let texts = self.texts(tags, platforms.len() * PLATFORM_TEXT_COUNT)?;
+        for (platform, texts) in platforms
+            .iter_mut()
+            .zip(texts.chunks_exact(PLATFORM_TEXT_COUNT))
+        {
+            [platform.name, platform.id, platform.code] = texts.to_vec().try_into().unwrap();
+        }
+        let size = self
+            .section::<u64>(STATION_PLATFORMS_INDEX)?
+            .len()
+            .saturating_sub(1);
+        let members = self.jagged::<u64>(STATION_PLATFORMS_INDEX, STATION_PLATFORMS, size)?;
+        let tags = [STATION_TEXTS_INDEX, STATION_TEXTS];
+        let texts = self.texts(tags, size * STATION_TEXT_COUNT)?;
+        let mut stations = vec![];
+        for (s, texts) in texts.chunks_exact(STATION_TEXT_COUNT).enumerate() {
+            let members: Vec<_> = members.get(s).iter().map(|p| *p as usize).collect();
+            if members.iter().any(|p| *p >= platforms.len()) {
+                return invalid(format!("station {} has an unknown platform", s));
+            }
+            let [id, name] = texts.to_vec().try_into().unwrap();
+            stations.push(Station {
+                id,
+                name,
+                platforms: members,
+            });
+        }
+        Ok(stations)
+    }
+
+    pub fn passages(&self, size: usize) -> Result<Vec<Vec<Passage>>, Error> {
+        let targets = self.jagged::<u64>(PASSAGES_INDEX, PASSAGE_TARGETS, size)?;
+        let times = self.section::<i64>(PASSAGE_TIMES)?;
+        if times.len() != targets.values.len() {
+            return invalid("passage sections do not agree".to_string());
+        }
+        Ok((0..targets.len())
+            .map(|i| {
+                let from = targets.index[i] as usize;
+                targets
+                    .get(i)
+                    .iter()
+                    .zip(&times[from..])
+                    .map(|(to, time)| Passage::new(*to as usize, *time))
+                    .collect()
+            })
+            .collect())
+    }
+
+    pub fn services(&self) -> Result<Vec<Service>, Error> {
+        let Some(weekdays) = self.optional::<u8>(SERVICE_WEEKDAYS)? else {
+            return Ok(vec![]);
+        };
+        let size = weekdays.len();
+        let ranges = self.section::<i32>(SERVICE_RANGES)?;
+        let added = self.jagged::<i32>(SERVICE_ADDED_INDEX, SERVICE_ADDED, size)?;
+        let removed = self.jagged::<i32>(SERVICE_REMOVED_INDEX, SERVICE_REMOVED, size)?;
+        if ranges.len() != 2 * size {
+            return invalid("service sections do not agree".to_string());
+        }
+        let date = |days: i32| {
+            NaiveDate::from_num_days_from_ce_opt(days)
+                .ok_or_else(|| Error::Format(format!("bad date {}", days)))
+        };
+        let mut services = vec![];
+        for (i, mask) in weekdays.iter().enumerate() {
+            let weekdays = std::array::from_fn(|day| mask & (1 << day) != 0);
+            let mut service =
+                Service::new(weekdays, date(ranges[2 * i])?, date(ranges[2 * i + 1])?);
+            for day in added.get(i) {
+                service.add(date(*day)?);
+            }
+            for day in removed.get(i) {
+                service.remove(date(*day)?);
+            }
+            services.push(service);
+        }
+        Ok(services)
+    }
+
+    pub fn changes(&self) -> Result<ChangeTimes, Error> {
+        let Some(global) = self.optional::<i64>(CHANGE_GLOBAL)? else {
+            return Ok(ChangeTimes::default());
+        };
+        let platforms = self.section::<i64>(CHANGE_PLATFORMS)?;
+        let pairs = self.section::<i64>(CHANGE_PAIRS)?;
+        if global.len() != 1 || platforms.len() % 2 != 0 || pairs.len() % 3 != 0 {
+            return invalid("change time sections do not agree".to_string());
+        }
+        let mut changes = ChangeTimes::new(global[0]);
+        for platform in platforms.chunks(2) {
+            changes.set_platform(platform[0] as usize, platform[1]);
+        }
+        for pair in pairs.chunks(3) {
+            changes.set_pair(pair[0] as usize, pair[1] as usize, pair[2]);
+        }
+        Ok(changes)
+    }
+
+    pub fn timezone(&self) -> Result<Tz, Error> {
+        let Some(name) = self.optional::<u8>(TIMEZONE)? else {
+            return Ok(Tz::UTC);
+        };
+        match std::str::from_utf8(name).ok().and_then(|n| n.parse().ok()) {
+            Some(timezone) => Ok(timezone),
+            None => invalid("unknown time zone".to_string()),
+        }
+    }
+
+    fn keys(&self, tags: [u32; 3]) -> Result<HashMap<String, i64>, Error> {
+        let Some(values) = self.optional::<i64>(tags[2])? else {
+            return Ok(HashMap::new());
+        };
+        let keys = self.jagged::<u8>(tags[0], tags[1], values.len())?;
+        let mut map = HashMap::new();
+        for (i, value) in values.iter().enumerate() {
+            let Ok(key) = std::str::from_utf8(keys.get(i)) else {
+                return invalid(format!("section {} has a bad key", tags[1]));
+            };
+            map.insert(key.to_string(), *value);
+        }
+        Ok(map)
+    }
+
+    pub fn trip_ids(&self) -> Result<HashMap<String, i32>, Error> {
+        let keys = self.keys([TRIP_KEYS_INDEX, TRIP_KEYS, TRIP_KEY_IDS])?;
+        Ok(keys.into_iter().map(|(k, v)| (k, v as i32)).collect())
+    }
+
+    pub fn stop_ids(&self) -> Result<HashMap<String, usize>, Error> {
+        let keys = self.keys([STOP_KEYS_INDEX, STOP_KEYS, STOP_KEY_PLATFORMS])?;
+        Ok(keys.into_iter().map(|(k, v)| (k, v as usize)).collect())
+    }
+
+    pub fn to_map(&self) -> Result<PublicTransport, Error> {
+        let mut platforms = self.platforms()?;
+        let stations = self.names(&mut platforms)?;
+        let routes = self.routes()?;
+        let passages = self.passages(platforms.len())?;
+        let services = self.services()?;
+        let changes = self.changes()?;
+        let timezone = self.timezone()?;
+        let trip_ids = self.trip_ids()?;
+        let stop_ids = self.stop_ids()?;
+        let map = PublicTransport::new(platforms, routes, passages)
+            .with_services(services)
+            .with_stations(stations)
+            .with_changes(changes)
+            .with_timezone(timezone)
+            .with_trip_ids(trip_ids)
+            .with_stop_ids(stop_ids);
+        check(&map)?;
+        Ok(map)
+    }
+}
+
+// Searches index by these without checks, so a corrupt image must not get that far
+fn check(map: &PublicTransport) -> Result<(), Error> {
+    let platforms = map.platforms.len();
+    for (i, platform) in map.platforms.iter().enumerate() {
+        let stops = |r: &usize| {
+            map.routes
+                .get(*r)
+                .is_some_and(|r| r.platforms().contains(&i))
+        };
+        if !platform.routes.iter().all(stops) {
+            return invalid(format!("platform {} lists a route not stopping there", i));
+        }
+    }
+    for (i, route) in map.routes.iter().enumerate() {
+        let stops = route.platforms().len() + route.circle as usize;
+        if route.platforms().is_empty() || route.platforms().iter().any(|p| *p >= platforms) {
+            return invalid(format!("route {} has bad platforms", i));
+        }
+        if route
+            .platforms()
+            .iter()
+            .any(|p| !map.platforms[*p].routes.contains(&i))
+        {
+            return invalid(format!("route {} stops at a platform not listing it", i));
+        }
+        for trip in route.trips().iter().chain(route.templates()) {
+            let sequences = trip.sequences().len();
+            if trip.stops().len() != stops
+                || (sequences != 0 && sequences != stops)
+                || trip.service().is_some_and(|s| s >= map.services.len())
+            {
+                return invalid(format!("route {} has a bad trip {}", i, trip.id()));
+            }
+        }
+    }
+    if map.passages.len() != platforms {
+        return invalid("passage sections do not agree".to_string());
+    }
+    for (i, passages) in map.passages.iter().enumerate() {
+        if passages.iter().any(|p| p.to >= platforms) {
+            return invalid(format!("platform {} has a passage to nowhere", i));
+        }
+    }
+    if map.stop_ids.values().any(|p| *p >= platforms) {
+        return invalid("stop ids refer to unknown platforms".to_string());
+    }
+    Ok(())
+}
+
+fn read_header(data: &[u8]) -> Result<HashMap<u32, Range<usize>>, Error> {
+    let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    let u64_at = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap()) as usize;
+    if data.len() < HEADER || &data[..8] != MAGIC {
+        return invalid("not a map image".to_string());
+    }
+    let version = u32_at(8);
+    if version > VERSION {
+        return invalid(format!("unsupported map image version {}", version));
+    }
+    let count = u32_at(12) as usize;
+    if data.len() < HEADER + count * ENTRY {
+        return invalid("truncated section table".to_string());
+    }
+    let mut sections = HashMap::new();
+    for i in 0..count {
+        let at = HEADER + i * ENTRY;
+        let offset = u64_at(at + 8);
+        let length = u64_at(at + 16);
+        if offset % ALIGN != 0
+            || offset
+                .checked_add(length)
+                .is_none_or(|end| end > data.len())
+        {
+            return invalid(format!("section {} is out of file", u32_at(at)));
+        }
+        sections.insert(u32_at(at), offset..offset + length);
+    }
+    Ok(sections)
+}
+
+pub fn read_image(filename: &str) -> Result<PublicTransport, Error> {
+    Image::open(filename)?.to_map()
+}
+
+#[cfg(test)]
+mod file {
+    use super::*;
+
+    use crate::realtime::Delay;
+
+    fn temp(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("tranet-{}-{}.tnb", name, std::process::id()));
+        path.to_string_lossy().to_string()
+    }
+
+    fn map() -> PublicTransport {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let mut service = Service::new(
+            [true, true, true, true, true, false, false],
+            date(1),
+            date(31),
+        );
+        service.add(date(6));
+        service.remove(date(8));
+        let mut changes = ChangeTimes::new(30);
+        changes.set_platform(1, 60);
+        changes.set_pair(0, 2, 90);
+        PublicTransport::new(
+            vec![
+                Platform::new(Point::new(55.1, 37.1), vec![0, 1])
+                    .with_id("A".to_string())
+                    .with_code("1".to_string()),
+                Platform::new(Point::new(55.2, 37.2), vec![0, 1]),
+                Platform::new(Point::new(55.3, 37.3), vec![1]).with_name("Depot".to_string()),
+            ],
+            vec![
+                Route::new(false, vec![0, 1], vec![Trip::new(1, vec![10, 20])]).with_info(
+                    RouteInfo {
+                        short_name: Some("7".to_string()),
+                        mode: Some(Mode::Tram),
+                        headsign: Some("Central".to_string()),
+                        ..RouteInfo::default()
+                    },
+                ),
+                Route::new(
+                    true,
+                    vec![0, 1, 2],
+                    vec![
+                        Trip::new(2, vec![10, 20, 30, 40])
+                            .with_service(0)
+                            .with_sequences(vec![1, 5, 7, 9]),
+                        Trip::new(3, vec![40, 50, 60, 70]),
+                        Trip::new(4, vec![0, 5, 10, 15])
+                            .with_frequencies(vec![Frequency::new(100, 200, 20)]),
+                    ],
+                ),
+            ],
+            vec![
+                vec![Passage::new(2, 100)],
+                vec![],
+                vec![Passage::new(0, 100)],
+            ],
+        )
+        .with_services(vec![service])
+        .with_stations(vec![Station {
+            id: Some("S".to_string()),
+            name: Some("Central".to_string()),
+            platforms: vec![0, 1],
+        }])
+        .with_changes(changes)
+        .with_timezone(Tz::Asia__Tokyo)
+        .with_trip_ids(HashMap::from([
+            ("T2".to_string(), 2),
+            ("T3".to_string(), 3),
+        ]))
+        .with_stop_ids(HashMap::from([("A".to_string(), 0)]))
+    }
+
+    #[test]
+    fn round_trip() {
+        let filename = temp("round-trip");
+        let expected = map();
+        write_image(&expected, &filename).unwrap();
+        assert!(is_image(&filename));
+        let map = read_image(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!(3, map.platforms.len());
+        assert_eq!(55.3, map.platforms[2].point.lat);
+        assert_eq!(vec![0, 1], map.platforms[1].routes);
+        assert_eq!(2, map.routes.len());
+        assert!(map.routes[1].circle);
+        assert_eq!(&[0, 1, 2], map.routes[1].platforms());
+        assert_eq!(&[40, 50, 60, 70], map.routes[1].trips()[1].stops());
+        assert_eq!(Some(0), map.routes[1].trips()[0].service());
+        assert_eq!(&[1, 5, 7, 9], map.routes[1].trips()[0].sequences());
+        assert!(map.routes[1].trips()[1].sequences().is_empty());
+        assert_eq!(None, map.routes[1].trips()[1].service());
+        assert_eq!(expected.routes[0].info, map.routes[0].info);
+        assert_eq!(RouteInfo::default(), map.routes[1].info);
+        let template = &map.routes[1].templates()[0];
+        assert_eq!(4, template.id());
+        assert_eq!(&[Frequency::new(100, 200, 20)], template.frequencies());
+        assert_eq!(100, map.passages[2][0].time);
+        assert_eq!(expected.services, map.services);
+        assert_eq!(expected.changes, map.changes);
+        assert_eq!(Tz::Asia__Tokyo, map.timezone);
+        assert_eq!(expected.trip_ids, map.trip_ids);
+        assert_eq!(expected.stop_ids, map.stop_ids);
+        let texts = |p: &Platform| (p.name.clone(), p.id.clone(), p.code.clone(), p.station);
+        let expected_texts: Vec<_> = expected.platforms.iter().map(texts).collect();
+        assert_eq!(
+            expected_texts,
+            map.platforms.iter().map(texts).collect::<Vec<_>>()
+        );
+        assert_eq!(expected.stations, map.stations);
+        assert_eq!(
+            Some("Central, platform 1".to_string()),
+            map.platform_name(0)
+        );
+    }
+
+    #[test]
+    fn delay_mapped_trip() {
+        let filename = temp("delay-mapped");
+        write_image(&map(), &filename).unwrap();
+        let mut map = read_image(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        assert!(map.delay_trip(3, &[Delay::new(1, 15)]));
+        let trip = &map.routes[1].trips()[1];
+        assert_eq!(&[40, 65, 75, 85], trip.stops());
+        assert_eq!(&[40, 50, 60, 70], trip.scheduled());
+    }
+
+    #[test]
+    fn not_image() {
+        let filename = temp("not-image");
+        std::fs::write(&filename, b"pickle").unwrap();
+        assert!(!is_image(&filename));
+        assert!(matches!(read_image(&filename), Err(Error::Format(_))));
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn bad_indices() {
+        let filename = temp("bad-indices");
+        let map = |routes, passages| {
+            PublicTransport::new(
+                (0..2)
+                    .map(|_| Platform::new(Point::new(55.1, 37.1), vec![0]))
+                    .collect(),
+                routes,
+                passages,
+            )
+        };
+        let route = |platforms, stops| Route::new(false, platforms, vec![Trip::new(1, stops)]);
+        let images = [
+            map(vec![route(vec![0, 2], vec![10, 20])], vec![vec![]; 2]),
+            map(vec![route(vec![0, 1], vec![10])], vec![vec![]; 2]),
+            map(
+                vec![route(vec![0, 1], vec![10, 20])],
+                vec![vec![Passage::new(2, 60)], vec![]],
+            ),
+            map(vec![], vec![vec![]; 2]),
+            map(vec![route(vec![0], vec![10])], vec![vec![]; 2]),
+            map(
+                vec![route(vec![0, 1], vec![10, 20]), route(vec![1], vec![30])],
+                vec![vec![]; 2],
+            ),
+        ];
+        for map in images {
+            write_image(&map, &filename).unwrap();
+            assert!(matches!(read_image(&filename), Err(Error::Format(_))));
+        }
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn truncated() {
+        let filename = temp("truncated");
+        write_image(&map(), &filename).unwrap();
+        let data = std::fs::read(&filename).unwrap();
+        std::fs::write(&filename, &data[..data.len() / 2]).unwrap();
+        assert!(matches!(read_image(&filename), Err(Error::Format(_))));
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
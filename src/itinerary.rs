@@ -0,0 +1,166 @@
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::map::{PlatformIndex, Point, PublicTransport, RouteIndex, Time};
+use crate::path::{Part, Path};
+
+// Times are seconds from the start of the service day, as everywhere in the map
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Itinerary {
+    pub origin: Point,
+    pub destination: Point,
+    pub date: Option<NaiveDate>,
+    pub timezone: Tz,
+    pub departure: Time,
+    pub arrival: Time,
+    pub departure_time: Option<String>,
+    pub arrival_time: Option<String>,
+    pub transfers: usize,
+    pub walking: Time,
+    pub legs: Vec<Leg>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Leg {
+    pub mode: Option<String>,
+    pub route: Option<RouteIndex>,
+    pub name: Option<String>,
+    pub headsign: Option<String>,
+    pub from: Option<Stop>,
+    pub to: Option<Stop>,
+    pub departure: Time,
+    pub arrival: Time,
+    pub waiting: Time,
+    pub walking: Time,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stop {
+    pub platform: PlatformIndex,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub point: Point,
+}
+
+impl Stop {
+    fn new(map: &PublicTransport, platform: PlatformIndex) -> Self {
+        Self {
+            platform,
+            id: map.platforms[platform].id.clone(),
+            name: map.platform_name(platform),
+            point: map.platforms[platform].point.clone(),
+        }
+    }
+}
+
+impl Leg {
+    fn new(map: &PublicTransport, part: &Part) -> Self {
+        let info = part.info();
+        Self {
+            mode: part.mode().map(str::to_string),
+            route: part.route(),
+            name: info.and_then(|i| i.name()).map(str::to_string),
+            headsign: info.and_then(|i| i.headsign.clone()),
+            from: part.from().map(|p| Stop::new(map, p)),
+            to: part.to().map(|p| Stop::new(map, p)),
+            departure: part.departure(),
+            arrival: part.arrival(),
+            waiting: part.waiting(),
+            walking: part.walking(),
+        }
+    }
+}
+
+impl Itinerary {
+    // The path is one completed by the planner, so it starts and ends at the query points
+    pub fn new(map: &PublicTransport, path: &Path) -> Self {
+        let origin = path.first().first();
+        let destination = path.last().last();
+        Self {
+            origin: Point::new(origin.y, origin.x),
+            destination: Point::new(destination.y, destination.x),
+            date: path.date,
+            timezone: path.timezone,
+            departure: path.departure,
+            arrival: path.arrival,
+            departure_time: path.departure_time().map(|t| t.to_rfc3339()),
+            arrival_time: path.arrival_time().map(|t| t.to_rfc3339()),
+            transfers: path.transfers(),
+            walking: path.walking,
+            legs: path.parts.iter().map(|p| Leg::new(map, p)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod json {
+    use super::*;
+
+    use geo_types::coord;
+
+    use crate::map::{Platform, Route, RouteInfo, Trip};
+
+    fn map() -> PublicTransport {
+        PublicTransport::new(
+            vec![
+                Platform::new(Point::new(0.0, 1.0), vec![0])
+                    .with_name("Kievskaya".to_string())
+                    .with_id("K".to_string()),
+                Platform::new(Point::new(0.0, 2.0), vec![0]).with_name("Arbatskaya".to_string()),
+            ],
+            vec![Route::new(
+                false,
+                vec![0, 1],
+                vec![Trip::new(1, vec![100, 200])],
+            )],
+            vec![vec![]; 2],
+        )
+    }
+
+    fn path() -> Path {
+        let walk = Part::new(vec![coord! {x: 0.5, y: 0.}, coord! {x: 1., y: 0.}], None)
+            .with_platforms(None, Some(0))
+            .with_times(40, 70)
+            .with_walking(30);
+        let ride = Part::new(vec![coord! {x: 1., y: 0.}, coord! {x: 2., y: 0.}], Some(0))
+            .with_info(RouteInfo {
+                short_name: Some("3".to_string()),
+                headsign: Some("Arbatskaya".to_string()),
+                ..RouteInfo::default()
+            })
+            .with_platforms(Some(0), Some(1))
+            .with_times(100, 200)
+            .with_waiting(30);
+        Path::new(vec![walk, ride], 40, 200).with_walking(30)
+    }
+
+    #[test]
+    fn legs() {
+        let itinerary = Itinerary::new(&map(), &path());
+        assert_eq!(0.5, itinerary.origin.lon);
+        assert_eq!(2.0, itinerary.destination.lon);
+        assert_eq!(0, itinerary.transfers);
+        assert_eq!(None, itinerary.departure_time);
+        let walk = &itinerary.legs[0];
+        assert_eq!(Some("walk".to_string()), walk.mode);
+        assert!(walk.from.is_none());
+        assert_eq!(Some("K".to_string()), walk.to.as_ref().unwrap().id);
+        let ride = &itinerary.legs[1];
+        assert_eq!(Some("3".to_string()), ride.name);
+        assert_eq!((100, 200, 30), (ride.departure, ride.arrival, ride.waiting));
+        let to = ride.to.as_ref().unwrap();
+        assert_eq!((1, Some("Arbatskaya")), (to.platform, to.name.as_deref()));
+    }
+
+    #[test]
+    fn round_trip() {
+        let itinerary = Itinerary::new(&map(), &path());
+        let text = serde_json::to_string(&itinerary).unwrap();
+        assert!(text.contains(r#""mode":"walk""#));
+        assert_eq!(itinerary, serde_json::from_str::<Itinerary>(&text).unwrap());
+        let path = path();
+        let text = serde_json::to_string(&path).unwrap();
+        assert_eq!(path, serde_json::from_str::<Path>(&text).unwrap());
+    }
+}
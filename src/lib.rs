@@ -1,6 +1,22 @@
+pub mod calendar;
+pub mod changes;
+pub mod directions;
+pub mod disruptions;
+pub mod gtfs;
+pub mod image;
+pub mod itinerary;
+pub mod lookup;
 pub mod map;
+pub mod mcraptor;
+pub mod options;
+pub mod passages;
 pub mod path;
 pub mod platforms;
 pub mod raptor;
 pub mod reader;
+pub mod realtime;
+pub mod reverse;
 pub mod searcher;
+pub mod spatial;
+pub mod streets;
+pub mod validation;
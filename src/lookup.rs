@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+
+use geo_types::Point;
+
+use crate::map::{PlatformIndex, PublicTransport};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub name: String,
+    pub platforms: Vec<PlatformIndex>,
+    pub distance: usize,
+}
+
+// Names are compared ignoring case and repeated spaces
+fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let rhs: Vec<char> = rhs.chars().collect();
+    let mut previous: Vec<usize> = (0..=rhs.len()).collect();
+    for (i, a) in lhs.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in rhs.iter().enumerate() {
+            let replace = previous[j] + (a != *b) as usize;
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[rhs.len()]
+}
+
+// Exact names score zero, names with a word starting with the query score one,
+// other names score by typos and are dropped when there are too many
+fn score(query: &str, name: &str) -> Option<usize> {
+    if query == name {
+        return Some(0);
+    }
+    if name.starts_with(query) || name.contains(&format!(" {}", query)) {
+        return Some(1);
+    }
+    let typos = edit_distance(query, name);
+    (typos <= query.chars().count() / 3).then_some(typos + 1)
+}
+
+impl PublicTransport {
+    // A stop id gives its platform, a station id all platforms of the station
+    pub fn find_by_id(&self, id: &str) -> Vec<PlatformIndex> {
+        if let Some(platform) = self.stop_ids.get(id) {
+            return vec![*platform];
+        }
+        self.stations
+            .iter()
+            .find(|s| s.id.as_deref() == Some(id))
+            .map(|s| s.platforms.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Vec<PlatformIndex> {
+        self.names()
+            .remove(&normalize(name))
+            .map(|(_, platforms)| platforms)
+            .unwrap_or_default()
+    }
+
+    pub fn find_similar(&self, name: &str, limit: usize) -> Vec<Match> {
+        let query = normalize(name);
+        let mut matches: Vec<_> = self
+            .names()
+            .into_iter()
+            .filter_map(|(normalized, (name, platforms))| {
+                let distance = score(&query, &normalized)?;
+                Some(Match {
+                    name,
+                    platforms,
+                    distance,
+                })
+            })
+            .collect();
+        matches.sort_by_key(|m| m.distance);
+        matches.truncate(limit);
+        matches
+    }
+
+    // A place is "id:" followed by a stop or station id, or a name of either
+    pub fn locate(&self, place: &str) -> Option<Point<f64>> {
+        let place = place.trim();
+        let platforms = match place.strip_prefix("id:") {
+            Some(id) => self.find_by_id(id.trim()),
+            None => match self.find_by_name(place) {
+                platforms if !platforms.is_empty() => platforms,
+                _ => self.find_similar(place, 1).pop()?.platforms,
+            },
+        };
+        self.center(&platforms)
+    }
+
+    pub fn center(&self, platforms: &[PlatformIndex]) -> Option<Point<f64>> {
+        if platforms.is_empty() {
+            return None;
+        }
+        let count = platforms.len() as f64;
+        let (lat, lon) = platforms.iter().fold((0.0, 0.0), |(lat, lon), p| {
+            let point = &self.platforms[*p].point;
+            (lat + point.lat, lon + point.lon)
+        });
+        Some(Point::new(lon / count, lat / count))
+    }
+
+    // Such as "Kievskaya, platform 2"
+    pub fn platform_name(&self, platform: PlatformIndex) -> Option<String> {
+        let platform = &self.platforms[platform];
+        let station = platform
+            .station
+            .and_then(|s| self.stations[s].name.as_ref());
+        let name = platform.name.as_ref().or(station)?;
+        match &platform.code {
+            Some(code) => Some(format!("{}, platform {}", name, code)),
+            None => Some(name.clone()),
+        }
+    }
+
+    // Station names cover all their platforms, as riders name the station
+    fn names(&self) -> BTreeMap<String, (String, Vec<PlatformIndex>)> {
+        let mut names: BTreeMap<String, (String, Vec<PlatformIndex>)> = BTreeMap::new();
+        let mut add = |name: &String, platform: PlatformIndex| {
+            let entry = names
+                .entry(normalize(name))
+                .or_insert_with(|| (name.clone(), vec![]));
+            if !entry.1.contains(&platform) {
+                entry.1.push(platform);
+            }
+        };
+        for station in &self.stations {
+            if let Some(name) = &station.name {
+                station.platforms.iter().for_each(|p| add(name, *p));
+            }
+        }
+        for (index, platform) in self.platforms.iter().enumerate() {
+            if let Some(name) = &platform.name {
+                add(name, index);
+            }
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod names {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::map::{Platform, Point as GeoPoint, Station};
+
+    fn map() -> PublicTransport {
+        PublicTransport::new(
+            vec![
+                Platform::new(GeoPoint::new(55.0, 37.0), vec![])
+                    .with_name("Kievskaya".to_string())
+                    .with_code("1".to_string()),
+                Platform::new(GeoPoint::new(55.2, 37.2), vec![]).with_code("2".to_string()),
+                Platform::new(GeoPoint::new(56.0, 38.0), vec![])
+                    .with_name("Park Kultury".to_string()),
+            ],
+            vec![],
+            vec![vec![]; 3],
+        )
+        .with_stations(vec![Station {
+            id: Some("S1".to_string()),
+            name: Some("Kievskaya".to_string()),
+            platforms: vec![0, 1],
+        }])
+        .with_stop_ids(HashMap::from([("P3".to_string(), 2)]))
+    }
+
+    #[test]
+    fn by_id() {
+        let map = map();
+        assert_eq!(vec![2], map.find_by_id("P3"));
+        assert_eq!(vec![0, 1], map.find_by_id("S1"));
+        assert!(map.find_by_id("X").is_empty());
+    }
+
+    #[test]
+    fn by_exact_name() {
+        let map = map();
+        assert_eq!(vec![0, 1], map.find_by_name("  kievskaya "));
+        assert_eq!(vec![2], map.find_by_name("Park  Kultury"));
+        assert!(map.find_by_name("Kiev").is_empty());
+    }
+
+    #[test]
+    fn by_similar_name() {
+        let map = map();
+        let matches = map.find_similar("Kievskya", 5);
+        assert_eq!(1, matches.len());
+        assert_eq!(
+            ("Kievskaya", 2),
+            (matches[0].name.as_str(), matches[0].distance)
+        );
+        let matches = map.find_similar("kultury", 5);
+        assert_eq!(vec![2], matches[0].platforms);
+        assert!(map.find_similar("Arbatskaya", 5).is_empty());
+    }
+
+    #[test]
+    fn locate() {
+        let map = map();
+        let point = map.locate("Kievskaya").unwrap();
+        assert!((point.x() - 37.1).abs() < 1e-9 && (point.y() - 55.1).abs() < 1e-9);
+        assert_eq!(Some(Point::new(38.0, 56.0)), map.locate("id:P3"));
+        assert_eq!(None, map.locate("id:X"));
+    }
+
+    #[test]
+    fn platform_name() {
+        let map = map();
+        assert_eq!(
+            Some("Kievskaya, platform 2".to_string()),
+            map.platform_name(1)
+        );
+        assert_eq!(Some("Park Kultury".to_string()), map.platform_name(2));
+    }
+}
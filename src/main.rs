@@ -1,25 +1,162 @@
-use std::{env, process::exit};
+use std::{env, path, process::exit};
 
-use chrono::{Local, Timelike};
+use chrono::Utc;
 
-use tranet::map::Time;
+use tranet::directions::Directions;
+use tranet::disruptions::read_disruptions;
+use tranet::gtfs::read_gtfs;
+use tranet::image::{is_image, read_image, write_image};
+use tranet::itinerary::Itinerary;
+use tranet::map::PublicTransport;
+use tranet::options::Options;
+use tranet::path::Path;
 use tranet::raptor::Raptor;
-use tranet::reader::{read_map, read_points};
+use tranet::reader::{read_map, read_queries};
+use tranet::streets::read_streets;
 
-fn main() {
-    let args = env::args().collect::<Vec<_>>();
-    if args.len() < 3 {
-        println!("Usage: tranet [map] [points]");
-        exit(1);
+fn fail(message: &str, error: impl std::fmt::Display) -> ! {
+    eprintln!("{}: {}", message, error);
+    exit(1);
+}
+
+fn load(filename: &String) -> PublicTransport {
+    if path::Path::new(filename).is_dir() || filename.ends_with(".zip") {
+        return read_gtfs(filename).unwrap_or_else(|e| fail("Can not read GTFS feed", e));
+    }
+    if is_image(filename) {
+        return read_image(filename).unwrap_or_else(|e| fail("Can not read map image", e));
     }
+    read_map(filename).unwrap_or_else(|e| fail("Can not read map", e))
+}
+
+fn usage() -> ! {
+    println!("Usage: tranet [map|gtfs] [points] [streets.osm.pbf] [--disruptions file]");
+    println!("                                                   [--format wkt|geojson|json|text]");
+    println!("       tranet convert [map|gtfs] [image]");
+    println!("       tranet validate [map|gtfs]");
+    exit(1);
+}
 
-    let departure = Local::now().num_seconds_from_midnight() as Time;
+fn convert(args: &[String]) {
+    if args.len() < 2 {
+        usage();
+    }
+    let map = load(&args[0]);
+    write_image(&map, &args[1]).unwrap_or_else(|e| fail("Can not write map image", e));
+}
 
-    let raptor = Raptor::new(read_map(&args[1]));
-    for (start, finish) in read_points(&args[2]) {
-        for path in raptor.find_path(departure, start, finish) {
-            println!("{}", path);
+fn validate(args: &[String]) {
+    if args.is_empty() {
+        usage();
+    }
+    let violations = load(&args[0]).validate();
+    for violation in &violations {
+        println!("{}", violation);
+    }
+    if !violations.is_empty() {
+        eprintln!("Found {} violations", violations.len());
+        exit(2);
+    }
+}
+
+// Takes the value of an option out of the arguments
+fn option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let position = args.iter().position(|a| a == name)?;
+    if position + 1 >= args.len() {
+        usage();
+    }
+    args.remove(position);
+    Some(args.remove(position))
+}
+
+fn print_wkt(path: &Path) {
+    if let (Some(departure), Some(arrival)) = (path.departure_time(), path.arrival_time()) {
+        println!("{} - {}", departure, arrival);
+    }
+    let rides: Vec<_> = path
+        .parts
+        .iter()
+        .filter_map(|p| p.info())
+        .map(|i| i.to_string())
+        .filter(|i| !i.is_empty())
+        .collect();
+    if !rides.is_empty() {
+        println!("{}", rides.join(", "));
+    }
+    println!("{}", path);
+}
+
+enum Format {
+    Wkt,
+    GeoJson,
+    Json,
+    Text,
+}
+
+// JSON gives a single line document for each query, the others blocks of lines
+fn print(format: &Format, map: &PublicTransport, paths: &[Path]) {
+    match format {
+        Format::Wkt => paths.iter().for_each(print_wkt),
+        Format::GeoJson => paths.iter().for_each(|p| println!("{}", p.to_geojson())),
+        Format::Text => {
+            for path in paths {
+                println!("{}\n", Directions::new(map, path));
+            }
+            return;
         }
-        println!();
+        Format::Json => {
+            let itineraries: Vec<_> = paths.iter().map(|p| Itinerary::new(map, p)).collect();
+            let json = serde_json::to_string(&itineraries)
+                .unwrap_or_else(|e| fail("Can not write itineraries", e));
+            println!("{}", json);
+            return;
+        }
+    }
+    println!();
+}
+
+fn search(args: &[String]) {
+    let mut args = args.to_vec();
+    let disruptions = option(&mut args, "--disruptions");
+    let format = match option(&mut args, "--format").as_deref() {
+        None | Some("wkt") => Format::Wkt,
+        Some("geojson") => Format::GeoJson,
+        Some("json") => Format::Json,
+        Some("text") => Format::Text,
+        Some(_) => usage(),
+    };
+    if args.len() < 2 {
+        usage();
+    }
+
+    let now = Utc::now();
+    let mut map = load(&args[0]);
+    if let Some(filename) = disruptions {
+        let disruptions =
+            read_disruptions(&filename).unwrap_or_else(|e| fail("Can not read disruptions", e));
+        map.disrupt(&disruptions)
+            .unwrap_or_else(|e| fail("Can not apply disruptions", e));
+    }
+    // Queries may name stops, so they are read while the map is at hand
+    let points = read_queries(&args[1], &map).unwrap_or_else(|e| fail("Can not read points", e));
+    let options = Options::default();
+    let mut raptor = Raptor::new(map);
+    if let Some(filename) = args.get(2) {
+        let streets = read_streets(filename).unwrap_or_else(|e| fail("Can not read streets", e));
+        raptor = raptor.with_streets(streets, &options.walking);
+    }
+    for (start, finish) in points {
+        let paths = raptor.find_path_at(&now, start, finish, options.clone());
+        print(&format, raptor.map(), &paths);
+    }
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("convert") => convert(&args[2..]),
+        Some("validate") => validate(&args[2..]),
+        Some(_) => search(&args[1..]),
+        None => usage(),
     }
 }
@@ -1,11 +1,23 @@
-use std::{collections::HashMap, ops::Range};
+use std::{cmp, collections::HashMap, fmt, ops::Deref, ops::Range, sync::Arc};
+
+use chrono_tz::Tz;
+use memmap2::Mmap;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::calendar::{Day, Service, ServiceIndex};
+use crate::changes::ChangeTimes;
+use crate::realtime::Delay;
+use crate::spatial::Grid;
 
 pub type Time = i64;
+pub type Cost = i64;
 pub type RouteIndex = usize;
 pub type PlatformIndex = usize;
+pub type StationIndex = usize;
 pub type OrdinalNumber = usize;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Point {
     pub lat: f64,
     pub lon: f64,
@@ -17,27 +29,284 @@ impl Point {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Platform {
     pub point: Point,
     pub routes: Vec<RouteIndex>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub station: Option<StationIndex>,
+    #[serde(default)]
+    closures: Vec<Range<Time>>,
 }
 
 impl Platform {
     pub fn new(point: Point, routes: Vec<RouteIndex>) -> Self {
-        Self { point, routes }
+        Self {
+            point,
+            routes,
+            name: None,
+            id: None,
+            code: None,
+            station: None,
+            closures: vec![],
+        }
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    // Tells platforms of a station apart, like "2" in "platform 2"
+    pub fn with_code(mut self, code: String) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn close(&mut self, interval: Range<Time>) {
+        self.closures.push(interval);
+    }
+
+    pub fn close_fully(&mut self) {
+        self.close(Time::MIN..Time::MAX);
+    }
+
+    pub fn reopen(&mut self) {
+        self.closures.clear();
+    }
+
+    pub fn is_open(&self, time: Time) -> bool {
+        !self.closures.iter().any(|c| c.contains(&time))
+    }
+}
+
+// Runs of a trip every headway seconds starting from start and before end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frequency {
+    pub start: Time,
+    pub end: Time,
+    pub headway: Time,
+}
+
+impl Frequency {
+    pub fn new(start: Time, end: Time, headway: Time) -> Self {
+        Self {
+            start,
+            end,
+            headway,
+        }
+    }
+
+    fn runs(&self) -> Time {
+        match self.end > self.start && self.headway > 0 {
+            true => (self.end - self.start - 1) / self.headway + 1,
+            false => 0,
+        }
+    }
+
+    // Start of the first run reaching a stop `pattern` after its start no earlier than time
+    fn next(&self, pattern: Time, time: Time) -> Option<Time> {
+        if self.runs() == 0 {
+            return None;
+        }
+        let wanted = time.saturating_sub(pattern).saturating_sub(self.start);
+        let run = wanted.div_euclid(self.headway) + (wanted.rem_euclid(self.headway) > 0) as Time;
+        let run = cmp::max(run, 0);
+        (run < self.runs()).then(|| self.start + run * self.headway)
+    }
+
+    fn previous(&self, pattern: Time, time: Time) -> Option<Time> {
+        if self.runs() == 0 {
+            return None;
+        }
+        let wanted = time.saturating_sub(pattern).saturating_sub(self.start);
+        let run = cmp::min(wanted.div_euclid(self.headway), self.runs() - 1);
+        (run >= 0).then(|| self.start + run * self.headway)
+    }
+}
+
+// Stop times of a trip, owned or read in place from a memory-mapped map image
+#[derive(Clone)]
+pub(crate) enum Times {
+    Owned(Vec<Time>),
+    Mapped(Arc<Mmap>, Range<usize>),
+}
+
+impl Times {
+    /// # Safety
+    /// The byte range must be inside the image and hold whole times aligned for them.
+    pub(crate) unsafe fn mapped(image: Arc<Mmap>, range: Range<usize>) -> Self {
+        Times::Mapped(image, range)
+    }
+
+    // Live times are written over a copy
+    fn to_mut(&mut self) -> &mut Vec<Time> {
+        if let Times::Mapped(..) = self {
+            *self = Times::Owned(self.to_vec());
+        }
+        match self {
+            Times::Owned(times) => times,
+            Times::Mapped(..) => unreachable!(),
+        }
+    }
+}
+
+impl Deref for Times {
+    type Target = [Time];
+
+    fn deref(&self) -> &[Time] {
+        match self {
+            Times::Owned(times) => times,
+            Times::Mapped(image, range) => {
+                let bytes = &image[range.clone()];
+                let size = bytes.len() / std::mem::size_of::<Time>();
+                unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const Time, size) }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Times {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Serialize for Times {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.deref().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Times {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(Times::Owned)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Trip {
     id: i32,
-    stops: Vec<Time>,
+    stops: Times,
+    #[serde(default)]
+    service: Option<ServiceIndex>,
+    #[serde(default)]
+    schedule: Option<Vec<Time>>,
+    #[serde(default)]
+    cancelled: bool,
+    #[serde(default)]
+    frequencies: Vec<Frequency>,
+    #[serde(default)]
+    sequences: Vec<u32>,
 }
 
 impl Trip {
     pub fn new(id: i32, stops: Vec<Time>) -> Self {
-        Self { id, stops }
+        Self::from_times(id, Times::Owned(stops))
+    }
+
+    pub(crate) fn from_times(id: i32, stops: Times) -> Self {
+        Self {
+            id,
+            stops,
+            service: None,
+            schedule: None,
+            cancelled: false,
+            frequencies: vec![],
+            sequences: vec![],
+        }
+    }
+
+    pub fn with_service(mut self, service: ServiceIndex) -> Self {
+        self.service = Some(service);
+        self
+    }
+
+    // The stops become a pattern repeated at every run of the frequencies
+    pub fn with_frequencies(mut self, frequencies: Vec<Frequency>) -> Self {
+        self.frequencies = frequencies;
+        self
+    }
+
+    pub fn frequencies(&self) -> &[Frequency] {
+        &self.frequencies
+    }
+
+    // Stop sequences of the feed, which only grow along the trip but may skip numbers
+    pub fn with_sequences(mut self, sequences: Vec<u32>) -> Self {
+        self.sequences = sequences;
+        self
+    }
+
+    pub fn sequences(&self) -> &[u32] {
+        &self.sequences
+    }
+
+    // Without sequences from the feed they count stops from one
+    pub fn ordinal_of(&self, sequence: u32) -> Option<OrdinalNumber> {
+        match self.sequences.is_empty() {
+            true => (sequence as usize)
+                .checked_sub(1)
+                .filter(|o| *o < self.stops.len()),
+            false => self.sequences.binary_search(&sequence).ok(),
+        }
+    }
+
+    pub fn is_frequent(&self) -> bool {
+        !self.frequencies.is_empty()
+    }
+
+    pub fn service(&self) -> Option<ServiceIndex> {
+        self.service
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn stops(&self) -> &[Time] {
+        &self.stops
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    pub fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+
+    pub fn scheduled(&self) -> &[Time] {
+        self.schedule.as_deref().unwrap_or(&self.stops)
+    }
+
+    // Each delay lasts until the next one, and a vehicle never leaves earlier than it arrived
+    pub fn delay(&mut self, delays: &[Delay]) {
+        let schedule = self.schedule.get_or_insert_with(|| self.stops.to_vec());
+        let mut delays = delays.to_vec();
+        delays.sort_by_key(|d| d.ordinal);
+        let mut delay = 0;
+        let mut next = delays.iter().peekable();
+        let mut previous = Time::MIN;
+        let stops = self.stops.to_mut().iter_mut();
+        for (ordinal, (stop, scheduled)) in stops.zip(schedule.iter()).enumerate() {
+            while let Some(d) = next.next_if(|d| d.ordinal <= ordinal) {
+                delay = d.time;
+            }
+            *stop = cmp::max(scheduled + delay, previous);
+            previous = *stop;
+        }
     }
 
     pub fn stop(&self, ordinal: OrdinalNumber, circle: bool) -> Time {
@@ -63,12 +332,137 @@ impl PartialEq for Trip {
     }
 }
 
-#[derive(Debug)]
+// A trip as it runs, shifted in time for a run of a frequent trip
+#[derive(Debug, Clone, Copy)]
+pub struct Departure<'a> {
+    trip: &'a Trip,
+    shift: Time,
+}
+
+impl<'a> Departure<'a> {
+    pub fn new(trip: &'a Trip, shift: Time) -> Self {
+        Self { trip, shift }
+    }
+
+    pub fn trip(&self) -> &'a Trip {
+        self.trip
+    }
+
+    pub fn id(&self) -> i32 {
+        self.trip.id()
+    }
+
+    pub fn shift(&self) -> Time {
+        self.shift
+    }
+
+    pub fn stop(&self, ordinal: OrdinalNumber, circle: bool) -> Time {
+        self.trip.stop(ordinal, circle) + self.shift
+    }
+
+    pub fn first(&self) -> Time {
+        self.trip.first() + self.shift
+    }
+
+    pub fn last(&self) -> Time {
+        self.trip.last() + self.shift
+    }
+}
+
+impl<'a> From<&'a Trip> for Departure<'a> {
+    fn from(trip: &'a Trip) -> Self {
+        Self::new(trip, 0)
+    }
+}
+
+impl PartialEq for Departure<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.trip == other.trip && self.shift == other.shift
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Tram,
+    Metro,
+    Rail,
+    Bus,
+    Ferry,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 5] = [Mode::Tram, Mode::Metro, Mode::Rail, Mode::Bus, Mode::Ferry];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Tram => "tram",
+            Mode::Metro => "metro",
+            Mode::Rail => "rail",
+            Mode::Bus => "bus",
+            Mode::Ferry => "ferry",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.name() == name)
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.name();
+        write!(f, "{}{}", name[..1].to_uppercase(), &name[1..])
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteInfo {
+    pub short_name: Option<String>,
+    pub long_name: Option<String>,
+    pub mode: Option<Mode>,
+    pub color: Option<String>,
+    pub agency: Option<String>,
+    pub headsign: Option<String>,
+}
+
+impl RouteInfo {
+    pub fn name(&self) -> Option<&str> {
+        self.short_name.as_deref().or(self.long_name.as_deref())
+    }
+}
+
+// Such as "Tram 7 toward Central", leaving out what is not known
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut words = vec![];
+        if let Some(mode) = self.mode {
+            words.push(mode.to_string());
+        }
+        if let Some(name) = self.name() {
+            words.push(name.to_string());
+        }
+        if let Some(headsign) = &self.headsign {
+            words.push(format!("toward {}", headsign));
+        }
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+// Only what the route is made from is serialized, lookups are rebuilt
+#[derive(Debug, Deserialize)]
+#[serde(from = "RouteData")]
 pub struct Route {
     pub circle: bool,
+    pub info: RouteInfo,
     platforms: Vec<PlatformIndex>,
     trips: Vec<Trip>,
+    templates: Vec<Trip>,
     ordinal: HashMap<PlatformIndex, OrdinalNumber>,
+    last: HashMap<PlatformIndex, OrdinalNumber>,
+    // Whether trips keep their order at every stop, so that they can be searched by time
+    fifo: bool,
 }
 
 impl Route {
@@ -79,22 +473,53 @@ impl Route {
             .rev()
             .map(|(index, platform)| (*platform, index))
             .collect();
+        let last = platforms
+            .iter()
+            .enumerate()
+            .map(|(index, platform)| (*platform, index))
+            .collect();
         let platforms = match circle {
             true => [platforms.as_slice(), platforms.as_slice()].concat(),
             false => platforms,
         };
+        let (templates, trips): (Vec<_>, Vec<_>) = trips.into_iter().partition(Trip::is_frequent);
+        let fifo = is_fifo(&trips);
         Self {
             circle,
+            info: RouteInfo::default(),
             platforms,
             trips,
+            templates,
             ordinal,
+            last,
+            fifo,
         }
     }
 
+    pub fn with_info(mut self, info: RouteInfo) -> Self {
+        self.info = info;
+        self
+    }
+
     fn seam(&self) -> OrdinalNumber {
         (self.platforms.len() / 2) - 1
     }
 
+    pub fn platforms(&self) -> &[PlatformIndex] {
+        match self.circle {
+            true => &self.platforms[..self.platforms.len() / 2],
+            false => &self.platforms,
+        }
+    }
+
+    pub fn trips(&self) -> &[Trip] {
+        &self.trips
+    }
+
+    pub fn templates(&self) -> &[Trip] {
+        &self.templates
+    }
+
     pub fn platform(&self, ordinal: OrdinalNumber) -> PlatformIndex {
         self.platforms[ordinal]
     }
@@ -103,49 +528,183 @@ impl Route {
         self.ordinal[lhs] < self.ordinal[rhs]
     }
 
+    pub fn is_after(&self, lhs: &PlatformIndex, rhs: &PlatformIndex) -> bool {
+        self.last[lhs] > self.last[rhs]
+    }
+
     pub fn is_seam(&self, ordinal: OrdinalNumber) -> bool {
         self.circle && ordinal == self.seam()
     }
 
-    fn has_earlier(&self, time: Time, ordinal: OrdinalNumber, trip: &Trip) -> bool {
+    pub fn is_reverse_seam(&self, ordinal: OrdinalNumber) -> bool {
+        self.circle && ordinal == self.seam() + 1
+    }
+
+    fn has_earlier(&self, time: Time, ordinal: OrdinalNumber, trip: &Departure) -> bool {
         time < trip.stop(ordinal, self.circle)
     }
 
-    fn next_trip(&self, time: Time, ordinal: OrdinalNumber) -> Option<&Trip> {
-        let i = self
-            .trips
-            .partition_point(|t| t.stop(ordinal, self.circle) < time);
-        self.trips.get(i)
+    // Runs of frequent trips are computed, not stored; a skipped run gives way to the one
+    // a step further
+    fn runs(
+        &self,
+        day: &Day,
+        at: &impl Fn(&Trip) -> Time,
+        (time, step): (Time, Time),
+        skipped: &[Departure],
+        run: impl Fn(&Frequency, Time, Time) -> Option<Time>,
+    ) -> Vec<Departure<'_>> {
+        let mut runs = vec![];
+        for trip in self.templates.iter().filter(|t| day.runs(t)) {
+            let pattern = at(trip) - trip.first();
+            for frequency in &trip.frequencies {
+                let mut time = time;
+                while let Some(start) = run(frequency, pattern, time) {
+                    let departure = Departure::new(trip, start - trip.first());
+                    if !skipped.contains(&departure) {
+                        runs.push(departure);
+                        break;
+                    }
+                    time = start + pattern + step;
+                }
+            }
+        }
+        runs
+    }
+
+    fn earliest(
+        &self,
+        time: Time,
+        day: &Day,
+        skipped: &[Departure],
+        at: impl Fn(&Trip) -> Time,
+    ) -> Option<Departure<'_>> {
+        let running = |t: &&Trip| day.runs(t) && !skipped.contains(&Departure::from(*t));
+        let scheduled = match self.fifo {
+            true => {
+                let i = self.trips.partition_point(|t| at(t) < time);
+                self.trips[i..].iter().find(running)
+            }
+            false => self
+                .trips
+                .iter()
+                .filter(|t| at(t) >= time && running(t))
+                .min_by_key(|t| at(t)),
+        };
+        let frequent = self.runs(day, &at, (time, 1), skipped, |f, pattern, time| {
+            f.next(pattern, time)
+        });
+        let arrival = |d: &Departure| at(d.trip) + d.shift;
+        scheduled
+            .map(Departure::from)
+            .into_iter()
+            .chain(frequent)
+            .min_by_key(arrival)
+    }
+
+    // Times at a stop of every trip of the day and every run of its frequent trips
+    pub fn times_at(&self, ordinal: OrdinalNumber, day: &Day, range: Range<Time>) -> Vec<Time> {
+        let at = |t: &Trip| t.stop(ordinal, self.circle);
+        let running = |t: &&Trip| day.runs(t);
+        let mut times: Vec<_> = self.trips.iter().filter(running).map(at).collect();
+        times.retain(|t| range.contains(t));
+        for trip in self.templates.iter().filter(running) {
+            let pattern = at(trip) - trip.first();
+            for frequency in &trip.frequencies {
+                let mut time = range.start;
+                while let Some(start) = frequency.next(pattern, time) {
+                    time = start + pattern;
+                    if !range.contains(&time) {
+                        break;
+                    }
+                    times.push(time);
+                    time += 1;
+                }
+            }
+        }
+        times
+    }
+
+    fn latest(
+        &self,
+        time: Time,
+        day: &Day,
+        skipped: &[Departure],
+        at: impl Fn(&Trip) -> Time,
+    ) -> Option<Departure<'_>> {
+        let running = |t: &&Trip| day.runs(t) && !skipped.contains(&Departure::from(*t));
+        let scheduled = match self.fifo {
+            true => {
+                let i = self.trips.partition_point(|t| at(t) <= time);
+                self.trips[..i].iter().rev().find(running)
+            }
+            false => self
+                .trips
+                .iter()
+                .filter(|t| at(t) <= time && running(t))
+                .max_by_key(|t| at(t)),
+        };
+        let frequent = self.runs(day, &at, (time, -1), skipped, |f, pattern, time| {
+            f.previous(pattern, time)
+        });
+        let departure = |d: &Departure| at(d.trip) + d.shift;
+        frequent
+            .into_iter()
+            .chain(scheduled.map(Departure::from))
+            .max_by_key(departure)
+    }
+
+    fn next_trip(
+        &self,
+        time: Time,
+        ordinal: OrdinalNumber,
+        day: &Day,
+        skipped: &[Departure],
+    ) -> Option<Departure<'_>> {
+        self.earliest(time, day, skipped, |t| t.stop(ordinal, self.circle))
     }
 
     fn try_catch_next_trip(
         &self,
         time: Time,
         ordinal: OrdinalNumber,
-        current_trip: Option<&Trip>,
-    ) -> Option<&Trip> {
+        current_trip: Option<Departure>,
+        day: &Day,
+        skipped: &[Departure],
+    ) -> Option<Departure<'_>> {
         match current_trip {
-            Some(trip) if !self.has_earlier(time, ordinal, trip) => None,
-            _ => self.next_trip(time, ordinal),
+            Some(trip) if !self.has_earlier(time, ordinal, &trip) => None,
+            _ => self.next_trip(time, ordinal, day, skipped),
         }
     }
 
-    fn next_trip_on_seam(&self, trip: &Trip) -> Option<&Trip> {
-        let time = *trip.last();
-        self.next_trip(time, 0)
+    // The trip a rider waiting at the seam from time leaves it on
+    pub fn leaving_seam(&self, time: Time, day: &Day) -> Option<Departure<'_>> {
+        self.next_trip(time, self.seam(), day, &[])
+    }
+
+    fn next_trip_on_seam(
+        &self,
+        trip: Departure,
+        day: &Day,
+        skipped: &[Departure],
+    ) -> Option<Departure<'_>> {
+        self.next_trip(trip.last(), 0, day, skipped)
     }
 
     fn try_catch_next_trip_on_seam(
         &self,
         time: Time,
-        current_trip: Option<&Trip>,
-    ) -> Option<&Trip> {
+        current_trip: Option<Departure>,
+        day: &Day,
+        skipped: &[Departure],
+    ) -> Option<Departure<'_>> {
         match current_trip {
-            Some(trip) => self.next_trip_on_seam(trip),
+            Some(trip) => self.next_trip_on_seam(trip, day, skipped),
             None => {
-                let trip = self.next_trip(time, self.seam());
+                let trip = self.leaving_seam(time, day);
                 match trip {
-                    Some(trip) => self.next_trip_on_seam(trip),
+                    Some(trip) => self.next_trip_on_seam(trip, day, skipped),
                     None => None,
                 }
             }
@@ -156,12 +715,112 @@ impl Route {
         &self,
         time: Time,
         ordinal: OrdinalNumber,
-        current_trip: Option<&Trip>,
-    ) -> Option<&Trip> {
+        current_trip: Option<Departure>,
+        day: &Day,
+    ) -> Option<Departure<'_>> {
+        self.try_catch_skipping(time, ordinal, current_trip, day, &[])
+    }
+
+    // Departures turned down already are passed over, even when another leaves at the
+    // same time
+    pub fn try_catch_skipping(
+        &self,
+        time: Time,
+        ordinal: OrdinalNumber,
+        current_trip: Option<Departure>,
+        day: &Day,
+        skipped: &[Departure],
+    ) -> Option<Departure<'_>> {
         match self.is_seam(ordinal) {
-            true => self.try_catch_next_trip_on_seam(time, current_trip),
-            false => self.try_catch_next_trip(time, ordinal, current_trip),
+            true => self.try_catch_next_trip_on_seam(time, current_trip, day, skipped),
+            false => self.try_catch_next_trip(time, ordinal, current_trip, day, skipped),
+        }
+    }
+
+    fn previous_trip(
+        &self,
+        time: Time,
+        ordinal: OrdinalNumber,
+        day: &Day,
+        skipped: &[Departure],
+    ) -> Option<Departure<'_>> {
+        self.latest(time, day, skipped, |t| t.stop(ordinal, self.circle))
+    }
+
+    fn try_catch_previous_trip(
+        &self,
+        time: Time,
+        ordinal: OrdinalNumber,
+        current_trip: Option<Departure>,
+        day: &Day,
+        skipped: &[Departure],
+    ) -> Option<Departure<'_>> {
+        match current_trip {
+            Some(trip) if time <= trip.stop(ordinal, self.circle) => None,
+            _ => self.previous_trip(time, ordinal, day, skipped),
+        }
+    }
+
+    fn previous_trip_on_seam(
+        &self,
+        time: Time,
+        day: &Day,
+        skipped: &[Departure],
+    ) -> Option<Departure<'_>> {
+        self.latest(time, day, skipped, |t| *t.last())
+    }
+
+    pub fn try_catch_back(
+        &self,
+        time: Time,
+        ordinal: OrdinalNumber,
+        current_trip: Option<Departure>,
+        day: &Day,
+    ) -> Option<Departure<'_>> {
+        self.try_catch_back_skipping(time, ordinal, current_trip, day, &[])
+    }
+
+    pub fn try_catch_back_skipping(
+        &self,
+        time: Time,
+        ordinal: OrdinalNumber,
+        current_trip: Option<Departure>,
+        day: &Day,
+        skipped: &[Departure],
+    ) -> Option<Departure<'_>> {
+        match (self.is_reverse_seam(ordinal), current_trip) {
+            (true, Some(trip)) => self.previous_trip_on_seam(trip.first(), day, skipped),
+            (true, None) => self.previous_trip_on_seam(time, day, skipped),
+            (false, _) => self.try_catch_previous_trip(time, ordinal, current_trip, day, skipped),
+        }
+    }
+
+    pub fn cancel_trip(&mut self, id: i32, cancelled: bool) -> bool {
+        let mut trips = self.trips.iter_mut().chain(self.templates.iter_mut());
+        match trips.find(|t| t.id() == id) {
+            Some(trip) => {
+                trip.set_cancelled(cancelled);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn delay_trip(&mut self, id: i32, delays: &[Delay]) -> bool {
+        if let Some(template) = self.templates.iter_mut().find(|t| t.id() == id) {
+            template.delay(delays);
+            return true;
+        }
+        let Some(trip) = self.trips.iter_mut().find(|t| t.id() == id) else {
+            return false;
+        };
+        trip.delay(delays);
+        if !self.trips.is_sorted_by(|a, b| a.stops() <= b.stops()) {
+            self.trips.sort_by(|a, b| a.stops().cmp(b.stops()));
         }
+        // A trip overtaking another partway is searched for stop by stop from now on
+        self.fifo = is_fifo(&self.trips);
+        true
     }
 
     pub fn range(&self, from: OrdinalNumber, to: OrdinalNumber) -> &[PlatformIndex] {
@@ -175,9 +834,27 @@ impl Route {
             false => from..self.platforms.len(),
         }
     }
+
+    pub fn head(&self, platform: &PlatformIndex) -> Range<OrdinalNumber> {
+        match self.circle {
+            true => {
+                let to = self.ordinal[platform];
+                (to + 1)..(to + self.platforms.len() / 2 + 1)
+            }
+            false => 0..(self.last[platform] + 1),
+        }
+    }
+}
+
+// Platforms grouped under a parent station
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Station {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub platforms: Vec<PlatformIndex>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Passage {
     pub to: PlatformIndex,
     pub time: Time,
@@ -189,27 +866,178 @@ impl Passage {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(from = "MapData")]
 pub struct PublicTransport {
     pub platforms: Vec<Platform>,
     pub routes: Vec<Route>,
     pub passages: Vec<Vec<Passage>>,
+    pub services: Vec<Service>,
+    pub stations: Vec<Station>,
+    pub changes: ChangeTimes,
+    pub timezone: Tz,
+    pub trip_ids: HashMap<String, i32>,
+    pub stop_ids: HashMap<String, PlatformIndex>,
+    pub grid: Grid,
 }
 
 impl PublicTransport {
     pub fn new(platforms: Vec<Platform>, routes: Vec<Route>, passages: Vec<Vec<Passage>>) -> Self {
+        let grid = Grid::new(&platforms);
         Self {
             platforms,
             routes,
             passages,
+            services: vec![],
+            stations: vec![],
+            changes: ChangeTimes::default(),
+            timezone: Tz::UTC,
+            trip_ids: HashMap::new(),
+            stop_ids: HashMap::new(),
+            grid,
         }
     }
+
+    pub fn with_services(mut self, services: Vec<Service>) -> Self {
+        self.services = services;
+        self
+    }
+
+    // Platforms learn their station from the list
+    pub fn with_stations(mut self, stations: Vec<Station>) -> Self {
+        for platform in &mut self.platforms {
+            platform.station = None;
+        }
+        for (index, station) in stations.iter().enumerate() {
+            for platform in &station.platforms {
+                self.platforms[*platform].station = Some(index);
+            }
+        }
+        self.stations = stations;
+        self
+    }
+
+    pub fn with_changes(mut self, changes: ChangeTimes) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn with_trip_ids(mut self, trip_ids: HashMap<String, i32>) -> Self {
+        self.trip_ids = trip_ids;
+        self
+    }
+
+    pub fn with_stop_ids(mut self, stop_ids: HashMap<String, PlatformIndex>) -> Self {
+        self.stop_ids = stop_ids;
+        self
+    }
+
+    pub fn cancel_trip(&mut self, id: i32, cancelled: bool) -> bool {
+        self.routes.iter_mut().any(|r| r.cancel_trip(id, cancelled))
+    }
+
+    pub fn is_open(&self, platform: PlatformIndex, time: Time) -> bool {
+        self.platforms[platform].is_open(time)
+    }
+
+    pub fn delay_trip(&mut self, id: i32, delays: &[Delay]) -> bool {
+        self.routes.iter_mut().any(|r| r.delay_trip(id, delays))
+    }
+}
+
+#[derive(Deserialize)]
+struct RouteData {
+    circle: bool,
+    #[serde(default)]
+    info: RouteInfo,
+    platforms: Vec<PlatformIndex>,
+    trips: Vec<Trip>,
+}
+
+fn is_fifo(trips: &[Trip]) -> bool {
+    trips.windows(2).all(|pair| {
+        let (before, after) = (pair[0].stops(), pair[1].stops());
+        before.len() == after.len() && before.iter().zip(after).all(|(b, a)| b <= a)
+    })
+}
+
+impl From<RouteData> for Route {
+    fn from(data: RouteData) -> Self {
+        Route::new(data.circle, data.platforms, data.trips).with_info(data.info)
+    }
+}
+
+impl Serialize for Route {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let trips: Vec<&Trip> = self.trips.iter().chain(&self.templates).collect();
+        let mut route = serializer.serialize_struct("Route", 4)?;
+        route.serialize_field("circle", &self.circle)?;
+        route.serialize_field("info", &self.info)?;
+        route.serialize_field("platforms", self.platforms())?;
+        route.serialize_field("trips", &trips)?;
+        route.end()
+    }
+}
+
+#[derive(Deserialize)]
+struct MapData {
+    platforms: Vec<Platform>,
+    routes: Vec<Route>,
+    passages: Vec<Vec<Passage>>,
+    #[serde(default)]
+    services: Vec<Service>,
+    #[serde(default)]
+    stations: Vec<Station>,
+    #[serde(default)]
+    changes: ChangeTimes,
+    #[serde(default)]
+    timezone: Tz,
+    #[serde(default)]
+    trip_ids: HashMap<String, i32>,
+    #[serde(default)]
+    stop_ids: HashMap<String, PlatformIndex>,
+}
+
+// The grid is built again from the platforms
+impl From<MapData> for PublicTransport {
+    fn from(data: MapData) -> Self {
+        PublicTransport::new(data.platforms, data.routes, data.passages)
+            .with_services(data.services)
+            .with_stations(data.stations)
+            .with_changes(data.changes)
+            .with_timezone(data.timezone)
+            .with_trip_ids(data.trip_ids)
+            .with_stop_ids(data.stop_ids)
+    }
+}
+
+impl Serialize for PublicTransport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_struct("PublicTransport", 9)?;
+        map.serialize_field("platforms", &self.platforms)?;
+        map.serialize_field("routes", &self.routes)?;
+        map.serialize_field("passages", &self.passages)?;
+        map.serialize_field("services", &self.services)?;
+        map.serialize_field("stations", &self.stations)?;
+        map.serialize_field("changes", &self.changes)?;
+        map.serialize_field("timezone", &self.timezone)?;
+        map.serialize_field("trip_ids", &self.trip_ids)?;
+        map.serialize_field("stop_ids", &self.stop_ids)?;
+        map.end()
+    }
 }
 
 #[cfg(test)]
 mod trip {
     use super::*;
 
+    use chrono::NaiveDate;
+
     fn route() -> Route {
         let trips = vec![
             Trip::new(1, vec![10, 60, 70]),
@@ -222,23 +1050,23 @@ mod trip {
     #[test]
     fn no_trip() {
         let route = route();
-        let trip = route.try_catch(60, 0, None);
+        let trip = route.try_catch(60, 0, None, &Day::default());
         assert!(trip.is_none());
     }
 
     #[test]
     fn yes_trip() {
         let route = route();
-        let trip = route.try_catch(70, 1, None);
+        let trip = route.try_catch(70, 1, None, &Day::default());
         assert!(trip.is_some());
-        assert_eq!(2, trip.unwrap().id);
+        assert_eq!(2, trip.unwrap().id());
     }
 
     #[test]
     fn no_move_trip() {
         let route = route();
-        let trip = &route.trips[0];
-        let trip = route.try_catch(60, 1, Some(trip));
+        let trip = Departure::from(&route.trips[0]);
+        let trip = route.try_catch(60, 1, Some(trip), &Day::default());
         assert!(trip.is_none());
     }
 
@@ -254,17 +1082,177 @@ mod trip {
     #[test]
     fn catch_circle_trip() {
         let route = circle_route();
-        let trip = route.try_catch(60, 1, None);
+        let trip = route.try_catch(60, 1, None, &Day::default());
         assert!(trip.is_some());
-        assert_eq!(1, trip.unwrap().id);
+        assert_eq!(1, trip.unwrap().id());
     }
 
     #[test]
     fn catch_circle_trip_on_seam() {
         let route = circle_route();
-        let trip = route.try_catch(70, 2, None);
+        let trip = route.try_catch(70, 2, None, &Day::default());
         assert!(trip.is_some());
-        assert_eq!(3, trip.unwrap().id);
+        assert_eq!(3, trip.unwrap().id());
+    }
+
+    #[test]
+    fn skip_cancelled_trip() {
+        let mut route = route();
+        assert!(route.cancel_trip(2, true));
+        let trip = route.try_catch(70, 1, None, &Day::default());
+        assert_eq!(3, trip.unwrap().id());
+        let trip = route.try_catch_back(100, 2, None, &Day::default());
+        assert_eq!(1, trip.unwrap().id());
+        route.cancel_trip(2, false);
+        let trip = route.try_catch(70, 1, None, &Day::default());
+        assert_eq!(2, trip.unwrap().id());
+    }
+
+    #[test]
+    fn skip_simultaneous_trip() {
+        let trips = vec![
+            Trip::new(1, vec![10, 60, 70]),
+            Trip::new(2, vec![30, 80, 90]),
+            Trip::new(3, vec![30, 90, 100]),
+            Trip::new(4, vec![50, 110, 120]),
+        ];
+        let route = Route::new(false, vec![0, 1, 2], trips);
+        let day = Day::default();
+        let closed = route.try_catch(20, 0, None, &day).unwrap();
+        assert_eq!(2, closed.id());
+        let trip = route.try_catch_skipping(30, 0, None, &day, &[closed]);
+        assert_eq!(3, trip.unwrap().id());
+        let both = [closed, trip.unwrap()];
+        let trip = route.try_catch_skipping(30, 0, None, &day, &both);
+        assert_eq!(4, trip.unwrap().id());
+        let trip = route.try_catch_back_skipping(30, 0, None, &day, &both[1..]);
+        assert_eq!(2, trip.unwrap().id());
+    }
+
+    fn frequent_route() -> Route {
+        let trips = vec![
+            Trip::new(1, vec![10, 60, 70]),
+            Trip::new(2, vec![0, 50, 60]).with_frequencies(vec![Frequency::new(100, 400, 100)]),
+        ];
+        Route::new(false, vec![0, 1, 2], trips)
+    }
+
+    #[test]
+    fn catch_frequent_trip() {
+        let route = frequent_route();
+        assert_eq!(1, route.templates().len());
+        let trip = route.try_catch(0, 0, None, &Day::default());
+        assert_eq!(1, trip.unwrap().id());
+        let trip = route.try_catch(11, 0, None, &Day::default()).unwrap();
+        assert_eq!((2, 100), (trip.id(), trip.first()));
+        let trip = route.try_catch(160, 1, None, &Day::default()).unwrap();
+        assert_eq!(250, trip.stop(1, false));
+        assert!(route
+            .try_catch(250, 1, Some(trip), &Day::default())
+            .is_none());
+        assert!(route.try_catch(351, 1, None, &Day::default()).is_none());
+        let closed = route.try_catch(11, 0, None, &Day::default()).unwrap();
+        let trip = route
+            .try_catch_skipping(100, 0, None, &Day::default(), &[closed])
+            .unwrap();
+        assert_eq!(200, trip.first());
+    }
+
+    #[test]
+    fn catch_previous_frequent_trip() {
+        let route = frequent_route();
+        let trip = route.try_catch_back(249, 1, None, &Day::default()).unwrap();
+        assert_eq!(150, trip.stop(1, false));
+        let trip = route
+            .try_catch_back(1000, 2, None, &Day::default())
+            .unwrap();
+        assert_eq!(360, trip.last());
+        let trip = route.try_catch_back(149, 1, None, &Day::default());
+        assert_eq!(1, trip.unwrap().id());
+    }
+
+    #[test]
+    fn describe_route() {
+        let info = RouteInfo {
+            short_name: Some("7".to_string()),
+            long_name: Some("Depot - Central".to_string()),
+            mode: Some(Mode::Tram),
+            headsign: Some("Central".to_string()),
+            ..RouteInfo::default()
+        };
+        assert_eq!("Tram 7 toward Central", info.to_string());
+        let info = RouteInfo {
+            short_name: None,
+            headsign: None,
+            ..info
+        };
+        assert_eq!("Tram Depot - Central", info.to_string());
+        assert_eq!(Some(Mode::Ferry), Mode::from_name("ferry"));
+    }
+
+    #[test]
+    fn closed_platform() {
+        let mut platform = Platform::new(Point::new(0.0, 0.0), vec![]);
+        platform.close(100..200);
+        assert!(platform.is_open(99));
+        assert!(!platform.is_open(100));
+        assert!(platform.is_open(200));
+        platform.close_fully();
+        assert!(!platform.is_open(0));
+        platform.reopen();
+        assert!(platform.is_open(150));
+    }
+
+    #[test]
+    fn catch_previous_trip() {
+        let route = route();
+        let trip = route.try_catch_back(95, 1, None, &Day::default());
+        assert_eq!(2, trip.unwrap().id());
+        let trip = route.try_catch_back(50, 1, None, &Day::default());
+        assert!(trip.is_none());
+    }
+
+    #[test]
+    fn no_move_previous_trip() {
+        let route = route();
+        let trip = Departure::from(&route.trips[1]);
+        let trip = route.try_catch_back(80, 1, Some(trip), &Day::default());
+        assert!(trip.is_none());
+    }
+
+    #[test]
+    fn catch_circle_trip_on_reverse_seam() {
+        let route = circle_route();
+        let trip = route.try_catch_back(100, 3, None, &Day::default());
+        assert_eq!(1, trip.unwrap().id());
+        let current = Departure::from(&route.trips[2]);
+        let trip = route.try_catch_back(200, 3, Some(current), &Day::default());
+        assert_eq!(1, trip.unwrap().id());
+    }
+
+    #[test]
+    fn skip_trip_not_running() {
+        let route = Route::new(
+            false,
+            vec![0, 1, 2],
+            vec![
+                Trip::new(1, vec![10, 60, 70]).with_service(1),
+                Trip::new(2, vec![30, 90, 100]).with_service(0),
+                Trip::new(3, vec![50, 110, 120]).with_service(1),
+            ],
+        );
+        let weekend = [false, false, false, false, false, true, true];
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let services = vec![
+            Service::new([true; 7], start, end),
+            Service::new(weekend, start, end),
+        ];
+        let monday = Day::new(&services, start);
+        let trip = route.try_catch(0, 0, None, &monday);
+        assert_eq!(2, trip.unwrap().id());
+        let trip = route.try_catch(40, 0, None, &monday);
+        assert!(trip.is_none());
     }
 
     #[test]
@@ -293,4 +1281,80 @@ mod trip {
         let route = loop_route();
         assert!(!route.is_before(&2, &1));
     }
+
+    #[test]
+    fn route_with_loop_head() {
+        let route = loop_route();
+        assert!(route.is_after(&1, &2));
+        assert_eq!(0..4, route.head(&1));
+        assert_eq!(0..3, route.head(&2));
+    }
+
+    #[test]
+    fn circle_route_head() {
+        let route = circle_route();
+        assert_eq!(2..5, route.head(&1));
+    }
+}
+
+#[cfg(test)]
+mod serialize {
+    use super::*;
+
+    fn map() -> PublicTransport {
+        let mut changes = ChangeTimes::new(30);
+        changes.set_pair(0, 1, 90);
+        PublicTransport::new(
+            vec![
+                Platform::new(Point::new(55.1, 37.1), vec![0]).with_id("A".to_string()),
+                Platform::new(Point::new(55.2, 37.2), vec![0]),
+            ],
+            vec![Route::new(
+                true,
+                vec![0, 1],
+                vec![
+                    Trip::new(1, vec![10, 20, 30]).with_service(0),
+                    Trip::new(2, vec![0, 10, 20])
+                        .with_frequencies(vec![Frequency::new(100, 200, 50)]),
+                ],
+            )
+            .with_info(RouteInfo {
+                mode: Some(Mode::Ferry),
+                ..RouteInfo::default()
+            })],
+            vec![vec![Passage::new(1, 60)], vec![]],
+        )
+        .with_stations(vec![Station {
+            id: Some("S".to_string()),
+            name: None,
+            platforms: vec![0, 1],
+        }])
+        .with_changes(changes)
+        .with_timezone(Tz::Europe__Moscow)
+        .with_stop_ids(HashMap::from([("A".to_string(), 0)]))
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let expected = map();
+        let text = serde_json::to_string(&expected).unwrap();
+        assert!(text.contains(r#""mode":"ferry""#));
+        let map: PublicTransport = serde_json::from_str(&text).unwrap();
+        let route = &map.routes[0];
+        assert!(route.circle);
+        assert_eq!(&[0, 1], route.platforms());
+        assert_eq!(Some(1), route.tail(&0).last());
+        assert_eq!(Some(0), route.trips()[0].service());
+        assert_eq!(
+            &[Frequency::new(100, 200, 50)],
+            route.templates()[0].frequencies()
+        );
+        assert_eq!(expected.routes[0].info, route.info);
+        assert_eq!(Some(0), map.platforms[1].station);
+        assert_eq!(expected.stations, map.stations);
+        assert_eq!(expected.changes, map.changes);
+        assert_eq!(60, map.passages[0][0].time);
+        assert_eq!(Tz::Europe__Moscow, map.timezone);
+        assert_eq!(expected.stop_ids, map.stop_ids);
+    }
 }
@@ -0,0 +1,393 @@
+use std::cmp;
+use std::rc::Rc;
+
+use chrono::NaiveDate;
+
+use crate::calendar::Day;
+use crate::map::{Cost, OrdinalNumber, PlatformIndex, PublicTransport, RouteIndex, Time};
+use crate::path::Path;
+use crate::platforms::Platforms;
+use crate::searcher::{accumulate, make_parts, Marked, Routes, Stop, Vehicle, Way};
+
+type LabelIndex = usize;
+type Bag = Vec<LabelIndex>;
+
+#[derive(Debug, Clone, Default)]
+pub struct Criteria {
+    pub transfers: bool,
+    pub walking: bool,
+    pub fares: Option<Vec<Cost>>,
+}
+
+impl Criteria {
+    pub fn new(transfers: bool, walking: bool) -> Self {
+        Self {
+            transfers,
+            walking,
+            fares: None,
+        }
+    }
+
+    pub fn with_fares(mut self, fares: Vec<Cost>) -> Self {
+        self.fares = Some(fares);
+        self
+    }
+
+    fn fare(&self, route: RouteIndex) -> Cost {
+        match &self.fares {
+            Some(fares) => fares.get(route).copied().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    fn dominates(&self, lhs: &Label, rhs: &Label) -> bool {
+        lhs.arrival <= rhs.arrival
+            && (!self.transfers || lhs.rides <= rhs.rides)
+            && (!self.walking || lhs.walking <= rhs.walking)
+            && (self.fares.is_none() || lhs.cost <= rhs.cost)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Label {
+    arrival: Time,
+    rides: usize,
+    walking: Time,
+    cost: Cost,
+    way: Option<Way>,
+    parent: Option<LabelIndex>,
+}
+
+impl Label {
+    fn start(arrival: Time, walking: Time) -> Self {
+        Self {
+            arrival,
+            rides: 0,
+            walking,
+            cost: 0,
+            way: None,
+            parent: None,
+        }
+    }
+
+    fn by_route(&self) -> bool {
+        matches!(&self.way, Some(way) if way.route.is_some())
+    }
+
+    fn finish(&self, duration: Time) -> Self {
+        Self {
+            arrival: self.arrival + duration,
+            walking: self.walking + duration,
+            ..self.clone()
+        }
+    }
+}
+
+struct Boarding<'a> {
+    vehicle: Vehicle<'a>,
+    parent: LabelIndex,
+}
+
+pub struct McSearcher<'a> {
+    map: &'a PublicTransport,
+    platforms: Platforms,
+    criteria: Criteria,
+    days: Rc<[Day]>,
+    departure: Time,
+    labels: Vec<Label>,
+    bags: Vec<Bag>,
+    journeys: Vec<(LabelIndex, Label)>,
+}
+
+impl<'a> McSearcher<'a> {
+    pub fn new(map: &'a PublicTransport, platforms: Platforms, criteria: Criteria) -> Self {
+        Self {
+            map,
+            platforms,
+            criteria,
+            days: Rc::from([Day::default()]),
+            departure: 0,
+            labels: vec![],
+            bags: vec![vec![]; map.platforms.len()],
+            journeys: vec![],
+        }
+    }
+
+    pub fn with_date(mut self, date: NaiveDate) -> Self {
+        self.days = Day::around(&self.map.services, &self.map.timezone, date);
+        self
+    }
+
+    pub fn ready(&self) -> bool {
+        !self.platforms.from.is_empty() && !self.platforms.to.is_empty()
+    }
+
+    pub fn run(&mut self, departure: Time) -> Vec<Path> {
+        let mut marked = self.init(departure);
+        let mut rides = 0;
+        while !marked.is_empty() {
+            rides += 1;
+            let routes = accumulate(self.map, marked);
+            marked = self.traverse(routes, rides);
+            marked.extend(self.transfer(&marked, rides));
+        }
+        self.paths()
+    }
+
+    fn init(&mut self, departure: Time) -> Marked {
+        self.departure = departure;
+        let mut marked = Marked::new();
+        let from: Vec<_> = self.platforms.from.iter().map(|(p, d)| (*p, *d)).collect();
+        for (platform, duration) in from {
+            let label = Label::start(departure + duration, duration);
+            if self.map.is_open(platform, label.arrival) && self.insert(platform, label) {
+                marked.insert(platform);
+            }
+        }
+        marked
+    }
+
+    fn traverse(&mut self, routes: Routes, rides: usize) -> Marked {
+        let map = self.map;
+        let mut marked = Marked::new();
+        for (r, p) in routes {
+            let route = &map.routes[r];
+            let fare = self.criteria.fare(r);
+            let mut boardings: Vec<Boarding> = vec![];
+            for ordinal in route.tail(&p) {
+                let platform = route.platform(ordinal);
+                for boarding in &mut boardings {
+                    let parent = &self.labels[boarding.parent];
+                    let label = Label {
+                        arrival: boarding.vehicle.arrival(ordinal),
+                        rides,
+                        walking: parent.walking,
+                        cost: parent.cost + fare,
+                        way: Some(boarding.vehicle.way(ordinal)),
+                        parent: Some(boarding.parent),
+                    };
+                    if self.map.is_open(platform, label.arrival) && self.insert(platform, label) {
+                        marked.insert(platform);
+                    }
+                    // Only a seam can change the trip of a vehicle already on its way
+                    boarding.vehicle.update(Time::MAX, ordinal);
+                }
+                for index in self.bags[platform].clone() {
+                    let label = &self.labels[index];
+                    if label.rides + 1 != rides {
+                        continue;
+                    }
+                    let mut vehicle = Vehicle::new(map, r, self.days.clone());
+                    vehicle.update(self.boarding(index, platform), ordinal);
+                    if vehicle.on_way() {
+                        let boarding = Boarding {
+                            vehicle,
+                            parent: index,
+                        };
+                        self.board(&mut boardings, boarding, ordinal);
+                    }
+                }
+            }
+        }
+        marked
+    }
+
+    // The first boarding after walking from the origin needs no change time
+    fn boarding(&self, index: LabelIndex, platform: PlatformIndex) -> Time {
+        let label = &self.labels[index];
+        let changes = &self.map.changes;
+        match (&label.way, label.parent) {
+            (Some(way), _) if way.route.is_some() => {
+                label.arrival + changes.time(platform, platform)
+            }
+            (Some(way), Some(parent)) => {
+                let alighting = self.labels[parent].arrival;
+                let change = alighting + changes.time(way.from.platform, platform);
+                cmp::max(label.arrival, change)
+            }
+            _ => label.arrival,
+        }
+    }
+
+    fn board<'b>(
+        &self,
+        boardings: &mut Vec<Boarding<'b>>,
+        boarding: Boarding<'b>,
+        ordinal: OrdinalNumber,
+    ) {
+        let dominates = |lhs: &Boarding, rhs: &Boarding| {
+            let lhs_label = &self.labels[lhs.parent];
+            let rhs_label = &self.labels[rhs.parent];
+            lhs.vehicle.arrival(ordinal) <= rhs.vehicle.arrival(ordinal)
+                && (!self.criteria.walking || lhs_label.walking <= rhs_label.walking)
+                && (self.criteria.fares.is_none() || lhs_label.cost <= rhs_label.cost)
+        };
+        if boardings.iter().any(|b| dominates(b, &boarding)) {
+            return;
+        }
+        boardings.retain(|b| !dominates(&boarding, b));
+        boardings.push(boarding);
+    }
+
+    fn transfer(&mut self, marked: &Marked, rides: usize) -> Marked {
+        let map = self.map;
+        let mut also_marked = Marked::new();
+        for from in marked {
+            let arrived: Vec<_> = self.bags[*from]
+                .iter()
+                .copied()
+                .filter(|i| self.labels[*i].rides == rides && self.labels[*i].by_route())
+                .collect();
+            for passage in &map.passages[*from] {
+                let way = Way::new(Stop::new(*from, None), Stop::new(passage.to, None), None);
+                for index in &arrived {
+                    let parent = &self.labels[*index];
+                    let arrival = parent.arrival + passage.time;
+                    let label = Label {
+                        arrival,
+                        rides,
+                        walking: parent.walking + passage.time,
+                        cost: parent.cost,
+                        way: Some(way.clone().with_times(parent.arrival, arrival)),
+                        parent: Some(*index),
+                    };
+                    let open = map.is_open(*from, parent.arrival)
+                        && map.is_open(passage.to, label.arrival);
+                    if open && self.insert(passage.to, label) {
+                        also_marked.insert(passage.to);
+                    }
+                }
+            }
+        }
+        also_marked
+    }
+
+    // With bag and target pruning
+    fn insert(&mut self, platform: PlatformIndex, label: Label) -> bool {
+        let criteria = &self.criteria;
+        if self
+            .journeys
+            .iter()
+            .any(|(_, j)| criteria.dominates(j, &label))
+        {
+            return false;
+        }
+        let labels = &self.labels;
+        let bag = &mut self.bags[platform];
+        if bag.iter().any(|i| criteria.dominates(&labels[*i], &label)) {
+            return false;
+        }
+        bag.retain(|i| !criteria.dominates(&label, &labels[*i]));
+        let index = self.labels.len();
+        bag.push(index);
+        if let Some(duration) = self.platforms.to.get(&platform) {
+            if label.by_route() {
+                self.journeys.push((index, label.finish(*duration)));
+            }
+        }
+        self.labels.push(label);
+        true
+    }
+
+    fn paths(&self) -> Vec<Path> {
+        let mut front: Vec<&(LabelIndex, Label)> = vec![];
+        for journey in &self.journeys {
+            if front
+                .iter()
+                .any(|j| self.criteria.dominates(&j.1, &journey.1))
+            {
+                continue;
+            }
+            front.retain(|j| !self.criteria.dominates(&journey.1, &j.1));
+            front.push(journey);
+        }
+        front
+            .into_iter()
+            .map(|(index, journey)| self.unwind(*index, journey))
+            .collect()
+    }
+
+    fn unwind(&self, index: LabelIndex, journey: &Label) -> Path {
+        let mut ways: Vec<&Way> = vec![];
+        let mut label = &self.labels[index];
+        while let (Some(way), Some(parent)) = (&label.way, label.parent) {
+            ways.push(way);
+            label = &self.labels[parent];
+        }
+        ways.reverse();
+        // The label left is the one of the first platform
+        let parts = make_parts(self.map, &ways, label.arrival);
+        Path::new(parts, self.departure, journey.arrival)
+            .with_walking(journey.walking)
+            .with_cost(journey.cost)
+    }
+}
+
+#[cfg(test)]
+mod bags {
+    use super::*;
+
+    use crate::map::{Passage, Platform, Point, Route, Trip};
+
+    fn map() -> PublicTransport {
+        PublicTransport::new(
+            vec![
+                Platform::new(Point::new(0.0, 0.0), vec![0, 1]),
+                Platform::new(Point::new(0.0, 1.0), vec![1]),
+                Platform::new(Point::new(0.0, 2.0), vec![2]),
+                Platform::new(Point::new(0.0, 3.0), vec![0, 2]),
+            ],
+            vec![
+                Route::new(false, vec![0, 3], vec![Trip::new(1, vec![10, 100])]),
+                Route::new(false, vec![0, 1], vec![Trip::new(2, vec![10, 20])]),
+                Route::new(false, vec![2, 3], vec![Trip::new(3, vec![40, 50])]),
+            ],
+            vec![vec![], vec![Passage::new(2, 10)], vec![], vec![]],
+        )
+    }
+
+    fn platforms() -> Platforms {
+        Platforms::from([(0, 5)].into(), [(3, 5)].into())
+    }
+
+    fn summary(paths: Vec<Path>) -> Vec<(Time, usize, Time, Cost)> {
+        paths
+            .iter()
+            .map(|p| (p.arrival, p.transfers(), p.walking, p.cost))
+            .collect()
+    }
+
+    #[test]
+    fn earliest_arrival() {
+        let map = map();
+        let mut searcher = McSearcher::new(&map, platforms(), Criteria::default());
+        assert_eq!(vec![(55, 1, 20, 0)], summary(searcher.run(0)));
+    }
+
+    #[test]
+    fn less_walking() {
+        let map = map();
+        let criteria = Criteria::new(false, true);
+        let mut searcher = McSearcher::new(&map, platforms(), criteria);
+        let expected = vec![(105, 0, 10, 0), (55, 1, 20, 0)];
+        assert_eq!(expected, summary(searcher.run(0)));
+    }
+
+    #[test]
+    fn cheaper() {
+        let map = map();
+        let criteria = Criteria::default().with_fares(vec![3, 2, 2]);
+        let mut searcher = McSearcher::new(&map, platforms(), criteria);
+        let expected = vec![(105, 0, 10, 3), (55, 1, 20, 4)];
+        assert_eq!(expected, summary(searcher.run(0)));
+    }
+
+    #[test]
+    fn same_arrival() {
+        let mut map = map();
+        map.routes[0] = Route::new(false, vec![0, 3], vec![Trip::new(1, vec![10, 50])]);
+        let criteria = Criteria::new(true, true);
+        let mut searcher = McSearcher::new(&map, platforms(), criteria);
+        assert_eq!(vec![(55, 0, 10, 0)], summary(searcher.run(0)));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::map::Time;
+use crate::platforms::WalkingProfile;
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub max_transfers: Option<usize>,
+    pub max_duration: Option<Time>,
+    pub max_walking: Option<Time>,
+    pub walking: WalkingProfile,
+}
+
+impl Options {
+    pub fn with_max_transfers(mut self, transfers: usize) -> Self {
+        self.max_transfers = Some(transfers);
+        self
+    }
+
+    pub fn with_max_duration(mut self, duration: Time) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    pub fn with_max_walking(mut self, walking: Time) -> Self {
+        self.max_walking = Some(walking);
+        self
+    }
+
+    pub fn with_walking_profile(mut self, walking: WalkingProfile) -> Self {
+        self.walking = walking;
+        self
+    }
+
+    pub fn has_round(&self, round: usize) -> bool {
+        match self.max_transfers {
+            Some(transfers) => round <= transfers + 1,
+            None => true,
+        }
+    }
+
+    pub fn latest(&self, departure: Time) -> Time {
+        match self.max_duration {
+            Some(duration) => departure.saturating_add(duration),
+            None => Time::MAX,
+        }
+    }
+
+    pub fn can_walk(&self, walking: Time) -> bool {
+        match self.max_walking {
+            Some(max) => walking <= max,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod limits {
+    use super::*;
+
+    #[test]
+    fn unlimited() {
+        let options = Options::default();
+        assert!(options.has_round(100));
+        assert_eq!(Time::MAX, options.latest(10));
+        assert!(options.can_walk(Time::MAX));
+    }
+
+    #[test]
+    fn limited() {
+        let options = Options::default()
+            .with_max_transfers(1)
+            .with_max_duration(60)
+            .with_max_walking(300);
+        assert!(options.has_round(2));
+        assert!(!options.has_round(3));
+        assert_eq!(70, options.latest(10));
+        assert!(options.can_walk(300));
+        assert!(!options.can_walk(301));
+    }
+}
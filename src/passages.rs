@@ -0,0 +1,153 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+use geo_types::Point;
+
+use crate::map::{Passage, PlatformIndex, PublicTransport, Time};
+use crate::platforms::{duration, WalkingProfile};
+
+impl PublicTransport {
+    pub fn with_generated_passages(self, radius: f64, profile: &WalkingProfile) -> Self {
+        self.generate_passages(radius, profile, None)
+    }
+
+    // Same, but no generated passage takes longer than max_walk
+    pub fn with_generated_passages_within(
+        self,
+        radius: f64,
+        profile: &WalkingProfile,
+        max_walk: Time,
+    ) -> Self {
+        self.generate_passages(radius, profile, Some(max_walk))
+    }
+
+    fn generate_passages(
+        mut self,
+        radius: f64,
+        profile: &WalkingProfile,
+        limit: Option<Time>,
+    ) -> Self {
+        let mut passages = self.nearby(radius, profile);
+        for (from, outgoing) in self.passages.iter().enumerate() {
+            passages[from].extend(outgoing.iter().cloned());
+        }
+        self.passages = closure(&passages, limit);
+        self
+    }
+
+    fn nearby(&self, radius: f64, profile: &WalkingProfile) -> Vec<Vec<Passage>> {
+        self.platforms
+            .iter()
+            .enumerate()
+            .map(|(from, platform)| {
+                let point = Point::new(platform.point.lon, platform.point.lat);
+                self.grid
+                    .within(&point, radius)
+                    .into_iter()
+                    .filter(|(to, _)| *to != from)
+                    .map(|(to, distance)| Passage::new(to, duration(distance, profile)))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// Every platform reachable on foot gets a direct passage with the shortest time
+pub fn transitive_closure(passages: &[Vec<Passage>]) -> Vec<Vec<Passage>> {
+    closure(passages, None)
+}
+
+// Given passages are kept even when longer than the limit
+fn closure(passages: &[Vec<Passage>], limit: Option<Time>) -> Vec<Vec<Passage>> {
+    (0..passages.len())
+        .map(|from| {
+            shortest(passages, from, limit)
+                .into_iter()
+                .map(|(to, time)| Passage::new(to, time))
+                .collect()
+        })
+        .collect()
+}
+
+fn shortest(
+    passages: &[Vec<Passage>],
+    from: PlatformIndex,
+    limit: Option<Time>,
+) -> BTreeMap<PlatformIndex, Time> {
+    let mut times = BTreeMap::from([(from, 0)]);
+    let mut heap = BinaryHeap::from([Reverse((0, from))]);
+    while let Some(Reverse((time, platform))) = heap.pop() {
+        if time > times[&platform] {
+            continue;
+        }
+        for passage in &passages[platform] {
+            let reached = time + passage.time;
+            let allowed = limit.is_none_or(|limit| reached <= limit) || platform == from;
+            if allowed && times.get(&passage.to).is_none_or(|t| reached < *t) {
+                times.insert(passage.to, reached);
+                heap.push(Reverse((reached, passage.to)));
+            }
+        }
+    }
+    times.remove(&from);
+    times
+}
+
+#[cfg(test)]
+mod footpaths {
+    use super::*;
+
+    use crate::map::{Platform, Point as GeoPoint};
+
+    // About 111 m between neighbouring platforms along a meridian
+    fn map() -> PublicTransport {
+        let platforms = (0..4)
+            .map(|i| Platform::new(GeoPoint::new(55.0 + 0.001 * i as f64, 37.0), vec![]))
+            .collect();
+        PublicTransport::new(platforms, vec![], vec![vec![]; 4])
+    }
+
+    fn summary(passages: &[Passage]) -> Vec<(PlatformIndex, Time)> {
+        passages.iter().map(|p| (p.to, p.time)).collect()
+    }
+
+    #[test]
+    fn within_radius() {
+        let map = map();
+        let passages = map.nearby(150.0, &WalkingProfile::default());
+        assert_eq!(vec![(1, 80)], summary(&passages[0]));
+        assert_eq!(vec![(0, 80), (2, 80)], summary(&passages[1]));
+    }
+
+    #[test]
+    fn closure() {
+        let map = map().with_generated_passages(150.0, &WalkingProfile::default());
+        assert_eq!(vec![(1, 80), (2, 160), (3, 240)], summary(&map.passages[0]));
+        assert_eq!(vec![(0, 240), (1, 160), (2, 80)], summary(&map.passages[3]));
+    }
+
+    #[test]
+    fn keep_given_passages() {
+        let mut map = map();
+        map.passages[0].push(Passage::new(3, 100));
+        let map = map.with_generated_passages(150.0, &WalkingProfile::default());
+        assert_eq!(vec![(1, 80), (2, 160), (3, 100)], summary(&map.passages[0]));
+        assert_eq!(vec![(0, 80), (2, 80), (3, 160)], summary(&map.passages[1]));
+    }
+
+    #[test]
+    fn max_walk() {
+        let mut map = map();
+        map.passages[0].push(Passage::new(3, 200));
+        let profile = WalkingProfile::default();
+        let map = map.with_generated_passages_within(150.0, &profile, 160);
+        assert_eq!(vec![(1, 80), (2, 160), (3, 200)], summary(&map.passages[0]));
+        assert_eq!(vec![(1, 160), (2, 80)], summary(&map.passages[3]));
+    }
+
+    #[test]
+    fn nothing_near() {
+        let map = map().with_generated_passages(50.0, &WalkingProfile::default());
+        assert!(map.passages.iter().all(|p| p.is_empty()));
+    }
+}
@@ -1,19 +1,123 @@
 use std::fmt;
 
+use chrono::{DateTime, Duration, NaiveDate};
+use chrono_tz::Tz;
 use geo_types::{Coord, Geometry, GeometryCollection, LineString};
+use geojson::{Feature, FeatureCollection};
+use serde::{Deserialize, Serialize};
 use wkt::ToWkt;
 
-use crate::map::{RouteIndex, Time};
+use crate::calendar::service_start;
+use crate::map::{Cost, PlatformIndex, RouteIndex, RouteInfo, Time};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Part {
     points: Vec<Coord<f64>>,
     route: Option<RouteIndex>,
+    info: Option<RouteInfo>,
+    from: Option<PlatformIndex>,
+    to: Option<PlatformIndex>,
+    departure: Time,
+    arrival: Time,
+    waiting: Time,
+    walking: Time,
 }
 
 impl Part {
     pub fn new(points: Vec<Coord<f64>>, route: Option<RouteIndex>) -> Self {
-        Self { points, route }
+        Self {
+            points,
+            route,
+            info: None,
+            from: None,
+            to: None,
+            departure: 0,
+            arrival: 0,
+            waiting: 0,
+            walking: 0,
+        }
+    }
+
+    pub fn with_info(mut self, info: RouteInfo) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    pub fn with_points(mut self, points: Vec<Coord<f64>>) -> Self {
+        self.points = points;
+        self
+    }
+
+    // Walks from or to the query points have a platform only on one side
+    pub fn with_platforms(
+        mut self,
+        from: Option<PlatformIndex>,
+        to: Option<PlatformIndex>,
+    ) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    pub fn with_times(mut self, departure: Time, arrival: Time) -> Self {
+        self.departure = departure;
+        self.arrival = arrival;
+        self
+    }
+
+    pub fn with_waiting(mut self, waiting: Time) -> Self {
+        self.waiting = waiting;
+        self
+    }
+
+    pub fn with_walking(mut self, walking: Time) -> Self {
+        self.walking = walking;
+        self
+    }
+
+    pub fn route(&self) -> Option<RouteIndex> {
+        self.route
+    }
+
+    pub fn info(&self) -> Option<&RouteInfo> {
+        self.info.as_ref()
+    }
+
+    pub fn from(&self) -> Option<PlatformIndex> {
+        self.from
+    }
+
+    pub fn to(&self) -> Option<PlatformIndex> {
+        self.to
+    }
+
+    pub fn departure(&self) -> Time {
+        self.departure
+    }
+
+    pub fn arrival(&self) -> Time {
+        self.arrival
+    }
+
+    // Time spent at the platform before the part starts
+    pub fn waiting(&self) -> Time {
+        self.waiting
+    }
+
+    pub fn walking(&self) -> Time {
+        self.walking
+    }
+
+    // Walks have no route, rides of routes without a known mode have no mode
+    pub fn mode(&self) -> Option<&'static str> {
+        match self.route {
+            Some(_) => self.info.as_ref()?.mode.map(|m| m.name()),
+            None => Some("walk"),
+        }
+    }
+
+    pub fn points(&self) -> &[Coord<f64>] {
+        &self.points
     }
 
     pub fn first(&self) -> &Coord<f64> {
@@ -25,15 +129,71 @@ impl Part {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Path {
     pub parts: Vec<Part>,
+    pub departure: Time,
     pub arrival: Time,
+    pub walking: Time,
+    pub cost: Cost,
+    pub date: Option<NaiveDate>,
+    pub timezone: Tz,
 }
 
 impl Path {
-    pub fn new(parts: Vec<Part>, arrival: Time) -> Self {
-        Self { parts, arrival }
+    pub fn new(parts: Vec<Part>, departure: Time, arrival: Time) -> Self {
+        Self {
+            parts,
+            departure,
+            arrival,
+            walking: 0,
+            cost: 0,
+            date: None,
+            timezone: Tz::UTC,
+        }
+    }
+
+    pub fn with_walking(mut self, walking: Time) -> Self {
+        self.walking = walking;
+        self
+    }
+
+    pub fn with_cost(mut self, cost: Cost) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    pub fn with_date(mut self, date: NaiveDate) -> Self {
+        self.date = Some(date);
+        self
+    }
+
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn departure_time(&self) -> Option<DateTime<Tz>> {
+        self.at(self.departure)
+    }
+
+    pub fn arrival_time(&self) -> Option<DateTime<Tz>> {
+        self.at(self.arrival)
+    }
+
+    pub fn at(&self, time: Time) -> Option<DateTime<Tz>> {
+        Some(service_start(&self.timezone, self.date?) + Duration::seconds(time))
+    }
+
+    pub fn transfers(&self) -> usize {
+        let rides = self.parts.iter().filter(|p| p.route.is_some()).count();
+        rides.saturating_sub(1)
+    }
+
+    pub fn dominates(&self, other: &Path) -> bool {
+        self.departure >= other.departure
+            && self.arrival <= other.arrival
+            && self.transfers() <= other.transfers()
     }
 
     pub fn first(&self) -> &Part {
@@ -45,17 +205,144 @@ impl Path {
     }
 }
 
+pub fn pareto(paths: Vec<Path>) -> Vec<Path> {
+    let mut front: Vec<Path> = vec![];
+    for path in paths {
+        if front.iter().any(|p| p.dominates(&path)) {
+            continue;
+        }
+        front.retain(|p| !path.dominates(p));
+        front.push(path);
+    }
+    front
+}
+
 impl fmt::Display for Path {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut routes = vec![];
         let mut collection = GeometryCollection::default();
         for part in &self.parts {
-            routes.push(part.route);
             let line = LineString::new(part.points.clone());
             collection.0.push(Geometry::LineString(line));
-            let point = part.last().clone().into();
+            let point = (*part.last()).into();
             collection.0.push(Geometry::Point(point));
         }
         write!(f, "{}", collection.to_wkt())
     }
 }
+
+impl Path {
+    // A line for each part, with points where a ride is boarded and alighted
+    pub fn to_geojson(&self) -> FeatureCollection {
+        let mut features = vec![];
+        for part in &self.parts {
+            let line = LineString::new(part.points.clone());
+            let mut feature = Feature::from(geojson::Value::from(&line));
+            feature.set_property("mode", part.mode());
+            feature.set_property("route", part.route);
+            if let Some(info) = &part.info {
+                feature.set_property("name", info.name());
+                feature.set_property("headsign", info.headsign.as_deref());
+                feature.set_property("color", info.color.as_deref());
+            }
+            self.set_time(&mut feature, "departure", part.departure);
+            self.set_time(&mut feature, "arrival", part.arrival);
+            feature.set_property("duration", part.arrival - part.departure);
+            feature.set_property("waiting", part.waiting);
+            features.push(feature);
+            if part.route.is_some() {
+                features.push(self.stop(part.first(), "board", part.from, part.departure));
+                features.push(self.stop(part.last(), "alight", part.to, part.arrival));
+            }
+        }
+        FeatureCollection {
+            bbox: None,
+            features,
+            foreign_members: None,
+        }
+    }
+
+    fn stop(
+        &self,
+        point: &Coord<f64>,
+        kind: &str,
+        platform: Option<PlatformIndex>,
+        time: Time,
+    ) -> Feature {
+        let point = geo_types::Point::from(*point);
+        let mut feature = Feature::from(geojson::Value::from(&point));
+        feature.set_property("stop", kind);
+        feature.set_property("platform", platform);
+        self.set_time(&mut feature, "time", time);
+        feature
+    }
+
+    // Times are seconds from the service day start, and also moments when the date is known
+    fn set_time(&self, feature: &mut Feature, name: &str, time: Time) {
+        feature.set_property(name, time);
+        if let Some(moment) = self.at(time) {
+            feature.set_property(format!("{}_time", name), moment.to_rfc3339());
+        }
+    }
+}
+
+#[cfg(test)]
+mod features {
+    use super::*;
+
+    use geo_types::coord;
+
+    use crate::map::Mode;
+
+    fn path() -> Path {
+        let walk = Part::new(vec![coord! {x: 0., y: 0.}, coord! {x: 1., y: 0.}], None)
+            .with_platforms(None, Some(0))
+            .with_times(100, 160)
+            .with_walking(60);
+        let ride = Part::new(
+            vec![
+                coord! {x: 1., y: 0.},
+                coord! {x: 2., y: 0.},
+                coord! {x: 3., y: 0.},
+            ],
+            Some(4),
+        )
+        .with_info(RouteInfo {
+            short_name: Some("7".to_string()),
+            mode: Some(Mode::Tram),
+            ..RouteInfo::default()
+        })
+        .with_platforms(Some(0), Some(2))
+        .with_times(200, 500)
+        .with_waiting(40);
+        Path::new(vec![walk, ride], 100, 500)
+    }
+
+    #[test]
+    fn parts_and_stops() {
+        let collection = path().to_geojson();
+        assert_eq!(4, collection.features.len());
+        let walk = &collection.features[0];
+        assert_eq!(Some(&"walk".into()), walk.property("mode"));
+        assert_eq!(Some(&60.into()), walk.property("duration"));
+        let ride = &collection.features[1];
+        assert_eq!(Some(&"tram".into()), ride.property("mode"));
+        assert_eq!(Some(&4.into()), ride.property("route"));
+        assert_eq!(Some(&"7".into()), ride.property("name"));
+        assert_eq!(Some(&300.into()), ride.property("duration"));
+        assert_eq!(Some(&40.into()), ride.property("waiting"));
+        assert!(!ride.contains_property("departure_time"));
+        let alight = &collection.features[3];
+        assert_eq!(Some(&"alight".into()), alight.property("stop"));
+        assert_eq!(Some(&2.into()), alight.property("platform"));
+        assert_eq!(Some(&500.into()), alight.property("time"));
+    }
+
+    #[test]
+    fn moments() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+        let path = path().with_date(date).with_timezone(Tz::Europe__Moscow);
+        let collection = path.to_geojson();
+        let time = collection.features[1].property("departure_time");
+        assert_eq!(Some(&"2024-01-10T00:03:20+03:00".into()), time);
+    }
+}
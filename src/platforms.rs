@@ -2,10 +2,45 @@ use std::collections::HashMap;
 
 use geo_types::Point;
 
-use crate::map::{Platform, PlatformIndex, Time};
+use crate::map::{PlatformIndex, PublicTransport, Time};
+use crate::spatial::Grid;
+use crate::streets::{self, Streets};
 
 pub type Walking = HashMap<PlatformIndex, Time>;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalkingProfile {
+    pub speed: f64,
+    pub access_radius: f64,
+    pub egress_radius: f64,
+    pub detour: f64,
+}
+
+impl WalkingProfile {
+    pub fn new(speed: f64, access_radius: f64, egress_radius: f64, detour: f64) -> Self {
+        Self {
+            speed,
+            access_radius,
+            egress_radius,
+            detour,
+        }
+    }
+
+    pub fn slow() -> Self {
+        Self::new(3000.0 / 3600.0, 500.0, 500.0, 1.3)
+    }
+
+    pub fn running_commuter() -> Self {
+        Self::new(8000.0 / 3600.0, 2000.0, 1500.0, 1.2)
+    }
+}
+
+impl Default for WalkingProfile {
+    fn default() -> Self {
+        Self::new(5000.0 / 3600.0, 1000.0, 1000.0, 1.0)
+    }
+}
+
 #[derive(Default)]
 pub struct Platforms {
     pub from: Walking,
@@ -14,12 +49,25 @@ pub struct Platforms {
 
 impl Platforms {
     pub fn new(
-        platforms: &Vec<Platform>,
+        grid: &Grid,
         start: geo_types::Point<f64>,
         finish: geo_types::Point<f64>,
+        profile: &WalkingProfile,
+    ) -> Self {
+        let from = find(grid, start, profile.access_radius, profile);
+        let to = find(grid, finish, profile.egress_radius, profile);
+        Self { from, to }
+    }
+
+    pub fn on_streets(
+        map: &PublicTransport,
+        streets: &Streets,
+        start: Point<f64>,
+        finish: Point<f64>,
+        profile: &WalkingProfile,
     ) -> Self {
-        let from = find(start, platforms);
-        let to = find(finish, platforms);
+        let from = walk(map, streets, start, profile.access_radius, profile);
+        let to = walk(map, streets, finish, profile.egress_radius, profile);
         Self { from, to }
     }
 
@@ -31,69 +79,114 @@ impl Platforms {
     }
 }
 
-fn find(point: Point<f64>, platforms: &Vec<Platform>) -> Walking {
-    let zone = zone(&point);
-    let point = to_utm_point(&point, zone);
-    let mut near = Walking::new();
-    for (index, platform) in platforms.iter().enumerate() {
-        let platform = Point::new(platform.point.lon, platform.point.lat);
-        let platform = to_utm_point(&platform, zone);
-        if is_near(&point, &platform) {
-            near.insert(index, duration(&point, &platform));
-        }
-    }
-    near
+fn find(grid: &Grid, point: Point<f64>, radius: f64, profile: &WalkingProfile) -> Walking {
+    grid.within(&point, radius)
+        .into_iter()
+        .map(|(index, distance)| (index, duration(distance, profile)))
+        .collect()
 }
 
-fn zone(point: &Point<f64>) -> u8 {
-    utm::lat_lon_to_zone_number(point.y(), point.x())
-}
-
-fn to_utm_point(point: &Point<f64>, zone: u8) -> Point<f64> {
-    let (x, y, _) = utm::to_utm_wgs84(point.y(), point.x(), zone);
-    Point::new(x, y)
-}
-
-fn is_near(lhs: &Point<f64>, rhs: &Point<f64>) -> bool {
-    let dx = lhs.x() - rhs.x();
-    let dy = lhs.y() - rhs.y();
-    let d2 = dx * dx + dy * dy;
-    let r = 1000.0;
-    d2 < r * r
+// A point away from the streets falls back to the straight line
+fn walk(
+    map: &PublicTransport,
+    streets: &Streets,
+    point: Point<f64>,
+    radius: f64,
+    profile: &WalkingProfile,
+) -> Walking {
+    if !streets.covers(&point) {
+        return find(&map.grid, point, radius, profile);
+    }
+    let candidates = map.grid.within(&point, radius);
+    let targets: Vec<_> = candidates
+        .iter()
+        .map(|(index, _)| {
+            let platform = &map.platforms[*index].point;
+            Point::new(platform.lon, platform.lat)
+        })
+        .collect();
+    let distances = streets.distances(&point, &targets, radius);
+    candidates
+        .into_iter()
+        .zip(distances)
+        .filter_map(|((index, _), distance)| Some((index, streets::duration(distance?, profile))))
+        .collect()
 }
 
-fn duration(from: &Point<f64>, to: &Point<f64>) -> Time {
-    let dx = from.x() - to.x();
-    let dy = from.y() - to.y();
-    let d2 = dx * dx + dy * dy;
-    let speed: f64 = 5000.0 / 3600.0;
-    (d2.sqrt() / speed).round() as Time
+pub(crate) fn duration(distance: f64, profile: &WalkingProfile) -> Time {
+    (distance * profile.detour / profile.speed).round() as Time
 }
 
 #[cfg(test)]
 mod point {
     use super::*;
 
-    use geo_types::coord;
+    use crate::map::{Platform, Point as GeoPoint};
+
+    fn grid() -> Grid {
+        Grid::new(&[Platform::new(GeoPoint::new(55.0, 37.0), vec![])])
+    }
 
     #[test]
     fn near() {
-        let lhs = coord! { x: 5.0, y: 5.0 };
-        let rhs = coord! { x: 1000.0, y: 5.0 };
-        assert!(is_near(&lhs.into(), &rhs.into()));
+        let point = geo_types::Point::new(37.0, 55.0089);
+        let walking = find(&grid(), point, 1000.0, &WalkingProfile::default());
+        assert_eq!(1, walking.len());
     }
 
     #[test]
     fn far() {
-        let lhs = coord! { x: 5.0, y: 5.0 };
-        let rhs = coord! { x: 1005.0, y: 5.0 };
-        assert!(!is_near(&lhs.into(), &rhs.into()));
+        let point = geo_types::Point::new(37.0, 55.0091);
+        let walking = find(&grid(), point, 1000.0, &WalkingProfile::default());
+        assert!(walking.is_empty());
     }
 
     #[test]
     fn calc_duration() {
-        let lhs = coord! { x: 5.0, y: 5.0 };
-        let rhs = coord! { x: 1005.0, y: 5.0 };
-        assert_eq!(720, duration(&lhs.into(), &rhs.into()));
+        let profile = WalkingProfile::default();
+        assert_eq!(720, duration(1000.0, &profile));
+    }
+
+    #[test]
+    fn slow_duration() {
+        let profile = WalkingProfile::slow();
+        assert_eq!(1560, duration(1000.0, &profile));
+    }
+
+    #[test]
+    fn across_river() {
+        let map = PublicTransport::new(
+            vec![Platform::new(GeoPoint::new(55.0, 37.002), vec![])],
+            vec![],
+            vec![vec![]],
+        );
+        let streets = Streets::new(
+            vec![
+                Point::new(37.0, 55.0),
+                Point::new(37.0, 55.005),
+                Point::new(37.002, 55.005),
+                Point::new(37.002, 55.0),
+            ],
+            &[vec![0, 1, 2, 3]],
+        );
+        let point = Point::new(37.0, 55.0);
+        let walking = walk(&map, &streets, point, 1000.0, &WalkingProfile::default());
+        assert!(walking.is_empty());
+        let walking = walk(&map, &streets, point, 2000.0, &WalkingProfile::default());
+        assert!(walking[&0] > 880);
+        let away = Point::new(37.002, 54.994);
+        let walking = walk(&map, &streets, away, 1000.0, &WalkingProfile::default());
+        assert_eq!(481, walking[&0]);
+    }
+
+    #[test]
+    fn wider_catchment() {
+        let grid = grid();
+        let near = geo_types::Point::new(37.0, 55.0135);
+        let walking = find(&grid, near, 1000.0, &WalkingProfile::default());
+        assert!(walking.is_empty());
+        let profile = WalkingProfile::running_commuter();
+        let walking = find(&grid, near, profile.access_radius, &profile);
+        assert_eq!(vec![0], walking.into_keys().collect::<Vec<_>>());
     }
 }
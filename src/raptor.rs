@@ -1,59 +1,276 @@
+use std::ops::Range;
+
+use chrono::{DateTime, NaiveDate, TimeZone};
 use geo_types::Coord;
 
+use crate::calendar::service_time;
 use crate::map::{PublicTransport, Time};
+use crate::mcraptor::{Criteria, McSearcher};
+use crate::options::Options;
 use crate::path::{Part, Path};
-use crate::platforms::Platforms;
+use crate::platforms::{Platforms, WalkingProfile};
+use crate::reverse::ReverseSearcher;
 use crate::searcher::Searcher;
+use crate::streets::Streets;
 
 type GeoPoint = geo_types::Point<f64>;
 
 pub struct Raptor {
     map: PublicTransport,
+    streets: Option<Streets>,
 }
 
 impl Raptor {
     pub fn new(map: PublicTransport) -> Self {
-        Self { map }
+        Self { map, streets: None }
+    }
+
+    pub fn map(&self) -> &PublicTransport {
+        &self.map
     }
 
-    pub fn find_path(&self, departure: Time, start: GeoPoint, finish: GeoPoint) -> Vec<Path> {
-        let platforms = Platforms::new(&self.map.platforms, start, finish);
-        let mut searcher = Searcher::new(&self.map, platforms);
+    // Queries right after an update see the live times
+    pub fn map_mut(&mut self) -> &mut PublicTransport {
+        &mut self.map
+    }
+
+    // Passages are walked at the pace of the given profile
+    pub fn with_streets(mut self, streets: Streets, walking: &WalkingProfile) -> Self {
+        streets.update_passages(&mut self.map, walking);
+        self.streets = Some(streets);
+        self
+    }
+
+    pub fn find_path(
+        &self,
+        date: NaiveDate,
+        departure: Time,
+        start: GeoPoint,
+        finish: GeoPoint,
+        options: Options,
+    ) -> Vec<Path> {
+        let platforms = self.platforms(start, finish, &options.walking);
+        let mut searcher = Searcher::new(&self.map, platforms, options).with_date(date);
         if searcher.ready() {
             let paths = searcher.run(departure);
-            return complete(paths, start, finish);
+            return self.complete(paths, date, start, finish);
+        }
+        vec![]
+    }
+
+    pub fn find_path_at<Z: TimeZone>(
+        &self,
+        moment: &DateTime<Z>,
+        start: GeoPoint,
+        finish: GeoPoint,
+        options: Options,
+    ) -> Vec<Path> {
+        let (date, departure) = service_time(&moment.with_timezone(&self.map.timezone));
+        self.find_path(date, departure, start, finish, options)
+    }
+
+    pub fn find_path_arriving(
+        &self,
+        date: NaiveDate,
+        arrival: Time,
+        start: GeoPoint,
+        finish: GeoPoint,
+        walking: &WalkingProfile,
+    ) -> Vec<Path> {
+        let platforms = self.platforms(start, finish, walking);
+        let mut searcher = ReverseSearcher::new(&self.map, platforms).with_date(date);
+        if searcher.ready() {
+            let paths = searcher.run(arrival);
+            return self.complete(paths, date, start, finish);
+        }
+        vec![]
+    }
+
+    pub fn find_profile(
+        &self,
+        date: NaiveDate,
+        window: Range<Time>,
+        start: GeoPoint,
+        finish: GeoPoint,
+        options: Options,
+    ) -> Vec<Path> {
+        let platforms = self.platforms(start, finish, &options.walking);
+        let mut searcher = Searcher::new(&self.map, platforms, options).with_date(date);
+        if searcher.ready() {
+            let paths = searcher.profile(window);
+            return self.complete(paths, date, start, finish);
+        }
+        vec![]
+    }
+
+    pub fn find_pareto(
+        &self,
+        date: NaiveDate,
+        departure: Time,
+        criteria: Criteria,
+        start: GeoPoint,
+        finish: GeoPoint,
+        walking: &WalkingProfile,
+    ) -> Vec<Path> {
+        let platforms = self.platforms(start, finish, walking);
+        let mut searcher = McSearcher::new(&self.map, platforms, criteria).with_date(date);
+        if searcher.ready() {
+            let paths = searcher.run(departure);
+            return self.complete(paths, date, start, finish);
         }
         vec![]
     }
-}
 
-fn complete(paths: Vec<Path>, from: GeoPoint, to: GeoPoint) -> Vec<Path> {
-    let from = from.into();
-    let to = to.into();
-    let mut completed = Vec::new();
-    for path in paths {
-        completed.push(make_path(&from, &to, path));
+    fn platforms(&self, start: GeoPoint, finish: GeoPoint, walking: &WalkingProfile) -> Platforms {
+        match &self.streets {
+            Some(streets) => Platforms::on_streets(&self.map, streets, start, finish, walking),
+            None => Platforms::new(&self.map.grid, start, finish, walking),
+        }
+    }
+
+    fn complete(
+        &self,
+        paths: Vec<Path>,
+        date: NaiveDate,
+        from: GeoPoint,
+        to: GeoPoint,
+    ) -> Vec<Path> {
+        let from = from.into();
+        let to = to.into();
+        let mut completed = Vec::new();
+        for path in paths {
+            let path = path.with_date(date).with_timezone(self.map.timezone);
+            let path = make_path(&from, &to, self.describe(path));
+            completed.push(match &self.streets {
+                Some(streets) => follow_streets(streets, path),
+                None => path,
+            });
+        }
+        completed.sort_by_key(|a| a.arrival);
+        completed
+    }
+
+    fn describe(&self, mut path: Path) -> Path {
+        path.parts = path
+            .parts
+            .into_iter()
+            .map(|part| match part.route() {
+                Some(route) => part.with_info(self.map.routes[route].info.clone()),
+                None => part,
+            })
+            .collect();
+        path
     }
-    completed.sort_by(|a, b| a.arrival.cmp(&b.arrival));
-    completed
 }
 
+// The walk ends where waiting for the first part begins
 fn make_first_walking(from: &Coord<f64>, path: &Path) -> Part {
-    let first = path.first().first();
-    Part::new(vec![*from, *first], None)
+    let first = path.first();
+    let arrival = first.departure() - first.waiting();
+    Part::new(vec![*from, *first.first()], None)
+        .with_platforms(None, first.from())
+        .with_times(path.departure, arrival)
+        .with_walking(arrival - path.departure)
 }
 
 fn make_last_walking(to: &Coord<f64>, path: &Path) -> Part {
-    let last = path.last().last();
-    Part::new(vec![*last, *to], None)
+    let last = path.last();
+    Part::new(vec![*last.last(), *to], None)
+        .with_platforms(last.to(), None)
+        .with_times(last.arrival(), path.arrival)
+        .with_walking(path.arrival - last.arrival())
 }
 
-fn make_path(from: &Coord<f64>, to: &Coord<f64>, path: Path) -> Path {
-    let first = make_first_walking(&from, &path);
-    let last = make_last_walking(&to, &path);
+fn make_path(from: &Coord<f64>, to: &Coord<f64>, mut path: Path) -> Path {
+    let first = make_first_walking(from, &path);
+    let last = make_last_walking(to, &path);
     let mut parts = vec![];
     parts.push(first);
-    parts.extend(path.parts);
+    parts.append(&mut path.parts);
     parts.push(last);
-    Path::new(parts, path.arrival)
+    path.parts = parts;
+    path
+}
+
+fn follow_streets(streets: &Streets, mut path: Path) -> Path {
+    path.parts = path
+        .parts
+        .into_iter()
+        .map(|part| {
+            if part.route().is_some() {
+                return part;
+            }
+            let from = (*part.first()).into();
+            let to = (*part.last()).into();
+            match streets.route(&from, &to) {
+                Some(points) => part.with_points(points),
+                None => part,
+            }
+        })
+        .collect();
+    path
+}
+
+#[cfg(test)]
+mod walks {
+    use super::*;
+
+    use geo_types::coord;
+
+    use crate::map::{Platform, Point, Route, Trip};
+
+    // Both platforms are about 640 metres from the query points
+    fn raptor() -> Raptor {
+        Raptor::new(PublicTransport::new(
+            vec![
+                Platform::new(Point::new(55.0, 37.0), vec![0]),
+                Platform::new(Point::new(55.0, 37.1), vec![0]),
+            ],
+            vec![Route::new(
+                false,
+                vec![0, 1],
+                vec![Trip::new(1, vec![3600, 4200])],
+            )],
+            vec![vec![]; 2],
+        ))
+    }
+
+    #[test]
+    fn profile_radius() {
+        let raptor = raptor();
+        let date = NaiveDate::default();
+        let (start, finish) = (GeoPoint::new(36.99, 55.0), GeoPoint::new(37.11, 55.0));
+        let walking = WalkingProfile::default();
+        let arriving = |walking| raptor.find_path_arriving(date, 7200, start, finish, walking);
+        assert_eq!(1, arriving(&walking).len());
+        assert!(arriving(&WalkingProfile::slow()).is_empty());
+        let criteria = Criteria::default();
+        let pareto =
+            |walking| raptor.find_pareto(date, 0, criteria.clone(), start, finish, walking);
+        assert_eq!(1, pareto(&walking).len());
+        assert!(pareto(&WalkingProfile::slow()).is_empty());
+    }
+
+    #[test]
+    fn first_and_last() {
+        let ride = Part::new(vec![coord! {x: 1., y: 0.}, coord! {x: 2., y: 0.}], Some(0))
+            .with_platforms(Some(3), Some(5))
+            .with_times(100, 200)
+            .with_waiting(30);
+        let path = Path::new(vec![ride], 40, 260);
+        let path = make_path(&coord! {x: 0., y: 0.}, &coord! {x: 3., y: 0.}, path);
+        let first = &path.parts[0];
+        assert_eq!((None, Some(3)), (first.from(), first.to()));
+        assert_eq!(
+            (40, 70, 30),
+            (first.departure(), first.arrival(), first.walking())
+        );
+        let last = &path.parts[2];
+        assert_eq!((Some(5), None), (last.from(), last.to()));
+        assert_eq!(
+            (200, 260, 60),
+            (last.departure(), last.arrival(), last.walking())
+        );
+        assert_eq!(30, path.parts[1].waiting());
+    }
 }
@@ -1,7 +1,9 @@
 use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicI32, Ordering};
 use std::{
     fs::File,
-    io::{BufRead, BufReader, Read},
+    io::{self, BufRead, BufReader, Read},
 };
 
 use geo_types::Point;
@@ -9,204 +11,549 @@ use serde_pickle::{value_from_reader, HashableValue, Value};
 use wkt::TryFromWkt;
 
 use crate::map;
-use crate::map::{Passage, Platform, PublicTransport, Route, Trip};
+use crate::map::{
+    Frequency, Mode, Passage, Platform, PublicTransport, Route, RouteInfo, Station, Time, Trip,
+};
+
+pub type Query = (Point<f64>, Point<f64>);
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Pickle(serde_pickle::Error),
+    Field(String, String),
+    Line(usize, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Pickle(e) => write!(f, "{}", e),
+            Error::Field(path, message) => write!(f, "{}: {}", path, message),
+            Error::Line(line, message) => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_pickle::Error> for Error {
+    fn from(e: serde_pickle::Error) -> Self {
+        Error::Pickle(e)
+    }
+}
+
+enum Location<'a> {
+    Root(&'static str),
+    Field(&'a Location<'a>, &'static str),
+    Item(&'a Location<'a>, usize),
+}
+
+impl<'a> Location<'a> {
+    fn field(&'a self, name: &'static str) -> Self {
+        Location::Field(self, name)
+    }
+
+    fn item(&'a self, index: usize) -> Self {
+        Location::Item(self, index)
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, Error> {
+        Err(Error::Field(self.to_string(), message.to_string()))
+    }
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Root(name) => write!(f, "{}", name),
+            Location::Field(parent, name) => write!(f, "{}.{}", parent, name),
+            Location::Item(parent, index) => write!(f, "{}[{}]", parent, index),
+        }
+    }
+}
 
-fn value_to_vec<'a>(value: &'a Value, default: &'a Vec<Value>) -> &'a Vec<Value> {
+trait FromValue: Sized {
+    fn from_value(value: &Value, at: &Location) -> Result<Self, Error>;
+}
+
+fn value_to_vec<'a>(value: &'a Value, at: &Location) -> Result<&'a Vec<Value>, Error> {
     match value {
-        Value::Tuple(items) => items,
-        Value::List(items) => items,
-        _ => default,
+        Value::Tuple(items) => Ok(items),
+        Value::List(items) => Ok(items),
+        _ => at.error("expected list"),
     }
 }
 
 fn value_to_dict<'a>(
     value: &'a Value,
-    default: &'a BTreeMap<HashableValue, Value>,
-) -> &'a BTreeMap<HashableValue, Value> {
+    at: &Location,
+) -> Result<&'a BTreeMap<HashableValue, Value>, Error> {
     match value {
-        Value::Dict(items) => items,
-        _ => default,
+        Value::Dict(items) => Ok(items),
+        _ => at.error("expected dict"),
     }
 }
 
-fn value_to_i64(value: &Value) -> i64 {
+fn value_to_f64(value: &Value, at: &Location) -> Result<f64, Error> {
     match value {
-        Value::I64(i) => *i,
-        _ => 0,
+        Value::F64(f) => Ok(*f),
+        Value::I64(i) => Ok(*i as f64),
+        _ => at.error("expected number"),
     }
 }
 
-fn hashvalue_to_i64(value: &HashableValue) -> i64 {
+fn value_to_bool(value: &Value, at: &Location) -> Result<bool, Error> {
     match value {
-        HashableValue::I64(i) => *i,
-        _ => 0,
+        Value::Bool(b) => Ok(*b),
+        _ => at.error("expected bool"),
     }
 }
 
-fn value_to_f64(value: &Value) -> f64 {
-    match value {
-        Value::F64(f) => *f,
-        _ => 0.0,
+fn get<'a>(
+    dict: &'a BTreeMap<HashableValue, Value>,
+    name: &'static str,
+    at: &Location,
+) -> Result<&'a Value, Error> {
+    match dict.get(&HashableValue::String(String::from(name))) {
+        Some(value) => Ok(value),
+        None => at.field(name).error("missing field"),
     }
 }
 
-fn value_to_bool(value: &Value) -> bool {
-    match value {
-        Value::Bool(b) => *b,
-        _ => false,
+fn get_string(
+    dict: &BTreeMap<HashableValue, Value>,
+    name: &'static str,
+    at: &Location,
+) -> Result<Option<String>, Error> {
+    match dict.get(&HashableValue::String(String::from(name))) {
+        None | Some(Value::None) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => at.field(name).error("expected string"),
     }
 }
 
-fn make_vec<T: for<'a> std::convert::From<&'a Value>>(value: &Value) -> Vec<T> {
-    value_to_vec(&value, &vec![])
+fn make_vec<T: FromValue>(value: &Value, at: &Location) -> Result<Vec<T>, Error> {
+    value_to_vec(value, at)?
         .iter()
-        .map(|v| v.into())
+        .enumerate()
+        .map(|(i, v)| T::from_value(v, &at.item(i)))
         .collect()
 }
 
-fn make_vec_of_i64(value: &Value) -> Vec<i64> {
-    value_to_vec(&value, &vec![])
-        .iter()
-        .map(|v| value_to_i64(v))
-        .collect()
+fn make_index(value: &Value, at: &Location) -> Result<usize, Error> {
+    match value {
+        Value::I64(i) if *i >= 0 => Ok(*i as usize),
+        _ => at.error("expected index"),
+    }
 }
 
-fn make_vec_of_index(value: &Value) -> Vec<usize> {
-    value_to_vec(&value, &vec![])
+fn make_key(value: &HashableValue, at: &Location) -> Result<usize, Error> {
+    match value {
+        HashableValue::I64(i) if *i >= 0 => Ok(*i as usize),
+        _ => at.error("expected index as key"),
+    }
+}
+
+fn make_vec_of_index(value: &Value, at: &Location) -> Result<Vec<usize>, Error> {
+    value_to_vec(value, at)?
         .iter()
-        .map(|v| make_index(v))
+        .enumerate()
+        .map(|(i, v)| make_index(v, &at.item(i)))
         .collect()
 }
 
-fn make_usize(value: &HashableValue) -> usize {
-    hashvalue_to_i64(&value) as usize
+fn make_time(value: &Value) -> Option<Time> {
+    match value {
+        Value::I64(i) => Some(*i),
+        Value::F64(f) if f.is_finite() => Some(*f as Time),
+        _ => None,
+    }
 }
 
-fn make_index(value: &Value) -> usize {
-    value_to_i64(&value) as usize
+fn make_vec_of_passage(value: &Value, size: usize) -> Result<Vec<Vec<Passage>>, Error> {
+    let at = Location::Root("passages");
+    let mut passages: Vec<Vec<Passage>> = vec![];
+    passages.resize_with(size, Default::default);
+    for (from, v) in value_to_dict(value, &at)?.iter() {
+        let from = make_key(from, &at)?;
+        let at = at.item(from);
+        if from >= size {
+            return at.error("unknown platform");
+        }
+        for (to, time) in value_to_dict(v, &at)?.iter() {
+            let to = make_key(to, &at)?;
+            let at = at.item(to);
+            if to >= size {
+                return at.error("unknown platform");
+            }
+            let Some(time) = make_time(time) else {
+                return at.error("expected number");
+            };
+            passages[from].push(Passage::new(to, time));
+        }
+    }
+    Ok(passages)
 }
 
-fn make_time(value: &Value) -> i64 {
-    value_to_f64(&value) as i64
+impl FromValue for map::Point {
+    fn from_value(value: &Value, at: &Location) -> Result<Self, Error> {
+        let point = value_to_dict(value, at)?;
+        let lat = value_to_f64(get(point, "lat", at)?, &at.field("lat"))?;
+        let lon = value_to_f64(get(point, "lon", at)?, &at.field("lon"))?;
+        Ok(map::Point::new(lat, lon))
+    }
 }
 
-fn make_vec_of_passage(value: &Value, size: usize) -> Vec<Vec<Passage>> {
-    let mut passages: Vec<Vec<Passage>> = vec![];
-    passages.resize_with(size, Default::default);
-    let default = BTreeMap::new();
-    for (from, v) in value_to_dict(&value, &default).iter() {
-        for (to, time) in value_to_dict(&v, &default).iter() {
-            passages[make_usize(&from)].push(Passage::new(make_usize(&to), make_time(time)));
-        }
+impl FromValue for Platform {
+    fn from_value(value: &Value, at: &Location) -> Result<Self, Error> {
+        let platform = value_to_dict(value, at)?;
+        let point = FromValue::from_value(get(platform, "point", at)?, &at.field("point"))?;
+        let routes = make_vec_of_index(get(platform, "routes", at)?, &at.field("routes"))?;
+        let mut result = Platform::new(point, routes);
+        result.name = get_string(platform, "name", at)?;
+        result.id = get_string(platform, "id", at)?;
+        result.code = get_string(platform, "code", at)?;
+        Ok(result)
     }
-    passages
 }
 
-impl From<&Value> for map::Point {
-    fn from(value: &Value) -> Self {
-        let default = BTreeMap::new();
-        let point = value_to_dict(&value, &default);
-        let lat = value_to_f64(
-            point
-                .get(&HashableValue::String(String::from("lat")))
-                .unwrap_or(&Value::None),
-        );
-        let lon = value_to_f64(
-            point
-                .get(&HashableValue::String(String::from("lon")))
-                .unwrap_or(&Value::None),
-        );
-        map::Point::new(lat, lon)
-    }
-}
-
-impl From<&Value> for Platform {
-    fn from(value: &Value) -> Self {
-        let default = BTreeMap::new();
-        let platform = value_to_dict(&value, &default);
-        let point = platform
-            .get(&HashableValue::String(String::from("point")))
-            .unwrap_or(&Value::None)
-            .into();
-        let routes = make_vec_of_index(
-            platform
-                .get(&HashableValue::String(String::from("routes")))
-                .unwrap_or(&Value::None),
-        );
-        Platform::new(point, routes)
+impl FromValue for Station {
+    fn from_value(value: &Value, at: &Location) -> Result<Self, Error> {
+        let station = value_to_dict(value, at)?;
+        let platforms = get(station, "platforms", at)?;
+        Ok(Station {
+            id: get_string(station, "id", at)?,
+            name: get_string(station, "name", at)?,
+            platforms: make_vec_of_index(platforms, &at.field("platforms"))?,
+        })
+    }
+}
+
+fn make_times(value: &Value) -> Option<Vec<Time>> {
+    match value {
+        Value::List(items) | Value::Tuple(items) => items.iter().map(make_time).collect(),
+        _ => None,
     }
 }
 
-impl From<&Value> for Trip {
-    fn from(value: &Value) -> Self {
-        let stops = make_vec_of_i64(&value);
-        static mut ID: i32 = 0;
-        unsafe {
-            let id = ID;
-            ID += 1;
-            Trip::new(id, stops)
+impl FromValue for Frequency {
+    fn from_value(value: &Value, at: &Location) -> Result<Self, Error> {
+        match make_times(value).as_deref() {
+            Some(&[start, end, headway]) => Ok(Frequency::new(start, end, headway)),
+            _ => at.error("expected start, end and headway"),
         }
     }
 }
 
-impl From<&Value> for Route {
-    fn from(value: &Value) -> Self {
-        let default = BTreeMap::new();
-        let route = value_to_dict(&value, &default);
-        let circle = value_to_bool(
-            route
-                .get(&HashableValue::String(String::from("circle")))
-                .unwrap_or(&Value::None),
-        );
-        let platforms = make_vec_of_index(
-            route
-                .get(&HashableValue::String(String::from("platforms")))
-                .unwrap_or(&Value::None),
-        );
-        let platforms = platforms.to_vec();
-        let trips = make_vec(
-            route
-                .get(&HashableValue::String(String::from("trips")))
-                .unwrap_or(&Value::None),
-        );
-        Route::new(circle, platforms, trips)
+// A trip is a list of stop times, or a dict with stops repeated at frequencies
+impl FromValue for Trip {
+    fn from_value(value: &Value, at: &Location) -> Result<Self, Error> {
+        static ID: AtomicI32 = AtomicI32::new(0);
+        let (stops, frequencies) = match value {
+            Value::Dict(trip) => {
+                let stops = get(trip, "stops", at)?;
+                let frequencies = get(trip, "frequencies", at)?;
+                let at = at.field("frequencies");
+                (make_times(stops), make_vec(frequencies, &at)?)
+            }
+            _ => (make_times(value), vec![]),
+        };
+        match stops {
+            Some(stops) => {
+                Ok(Trip::new(ID.fetch_add(1, Ordering::Relaxed), stops)
+                    .with_frequencies(frequencies))
+            }
+            None => at.error("expected list of numbers"),
+        }
     }
 }
 
-impl From<&Value> for PublicTransport {
-    fn from(value: &Value) -> Self {
-        let default = vec![];
-        let fields = value_to_vec(&value, &default);
-        let platforms = make_vec(fields.get(0).unwrap_or(&Value::None));
-        let routes = make_vec(fields.get(1).unwrap_or(&Value::None));
-        let passages = make_vec_of_passage(fields.get(2).unwrap_or(&Value::None), platforms.len());
-        Self {
-            platforms,
-            routes,
-            passages,
+impl FromValue for Route {
+    fn from_value(value: &Value, at: &Location) -> Result<Self, Error> {
+        let route = value_to_dict(value, at)?;
+        let circle = value_to_bool(get(route, "circle", at)?, &at.field("circle"))?;
+        let platforms = make_vec_of_index(get(route, "platforms", at)?, &at.field("platforms"))?;
+        let trips = make_vec(get(route, "trips", at)?, &at.field("trips"))?;
+        let mode = match get_string(route, "mode", at)? {
+            Some(name) => match Mode::from_name(&name) {
+                Some(mode) => Some(mode),
+                None => return at.field("mode").error("unknown mode"),
+            },
+            None => None,
+        };
+        // Descriptive fields are all optional
+        let info = RouteInfo {
+            short_name: get_string(route, "short_name", at)?,
+            long_name: get_string(route, "long_name", at)?,
+            mode,
+            color: get_string(route, "color", at)?,
+            agency: get_string(route, "agency", at)?,
+            headsign: get_string(route, "headsign", at)?,
+        };
+        Ok(Route::new(circle, platforms, trips).with_info(info))
+    }
+}
+
+impl FromValue for PublicTransport {
+    fn from_value(value: &Value, at: &Location) -> Result<Self, Error> {
+        let fields = value_to_vec(value, at)?;
+        if fields.len() < 3 {
+            return at.error("expected platforms, routes and passages");
+        }
+        let platforms: Vec<Platform> = make_vec(&fields[0], &Location::Root("platforms"))?;
+        let routes = make_vec(&fields[1], &Location::Root("routes"))?;
+        let passages = make_vec_of_passage(&fields[2], platforms.len())?;
+        // Stations are optional and come after passages
+        let stations: Vec<Station> = match fields.get(3) {
+            Some(stations) => make_vec(stations, &Location::Root("stations"))?,
+            None => vec![],
+        };
+        for (s, station) in stations.iter().enumerate() {
+            if station.platforms.iter().any(|p| *p >= platforms.len()) {
+                return Location::Root("stations").item(s).error("unknown platform");
+            }
         }
+        let stop_ids = platforms
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| Some((p.id.clone()?, i)))
+            .collect();
+        Ok(Self::new(platforms, routes, passages)
+            .with_stations(stations)
+            .with_stop_ids(stop_ids))
+    }
+}
+
+pub fn read_map(filename: &str) -> Result<PublicTransport, Error> {
+    let reader: Box<dyn Read> = Box::new(BufReader::new(File::open(filename)?));
+    let decoded: Value = value_from_reader(reader, Default::default())?;
+    PublicTransport::from_value(&decoded, &Location::Root("map"))
+}
+
+// A point is given in WKT, or as a place the map can locate
+fn parse_point(point: Option<&str>, map: Option<&PublicTransport>) -> Result<Point<f64>, String> {
+    let point = point
+        .ok_or("expected two points separated by comma")?
+        .trim();
+    match (Point::try_from_wkt_str(point), map) {
+        (Ok(point), _) => Ok(point),
+        (Err(_), Some(map)) => map.locate(point).ok_or(format!("unknown place {}", point)),
+        (Err(e), None) => Err(e.to_string()),
     }
 }
 
-pub fn read_map(filename: &String) -> PublicTransport {
-    let reader: Box<dyn Read> = Box::new(File::open(&filename).expect("Can not open map"));
-    let decoded: Value = value_from_reader(reader, Default::default()).expect("Can not parse map");
-    (&decoded).into()
+fn parse_query(line: &str, map: Option<&PublicTransport>) -> Result<Query, String> {
+    let mut points = line.split(',');
+    Ok((
+        parse_point(points.next(), map)?,
+        parse_point(points.next(), map)?,
+    ))
+}
+
+pub fn read_points(filename: &str) -> Result<Vec<Query>, Error> {
+    read_lines(filename, None)
 }
 
-fn parse_points(line: &String) -> (Point<f64>, Point<f64>) {
-    let points: Vec<&str> = line.split(',').collect();
-    (
-        Point::try_from_wkt_str(points[0]).unwrap(),
-        Point::try_from_wkt_str(points[1]).unwrap(),
-    )
+// Like points, but either end may be a stop name or "id:" with a stop id
+pub fn read_queries(filename: &str, map: &PublicTransport) -> Result<Vec<Query>, Error> {
+    read_lines(filename, Some(map))
 }
 
-pub fn read_points(filename: &String) -> Vec<(Point<f64>, Point<f64>)> {
+fn read_lines(filename: &str, map: Option<&PublicTransport>) -> Result<Vec<Query>, Error> {
     let mut points = Vec::new();
-    let file = File::open(filename).expect("Can not open points");
-    for line in BufReader::new(file).lines().flatten() {
-        points.push(parse_points(&line));
+    let file = File::open(filename)?;
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        points.push(parse_query(&line, map).map_err(|e| Error::Line(i + 1, e))?);
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod errors {
+    use super::*;
+
+    fn dict(items: Vec<(&str, Value)>) -> Value {
+        Value::Dict(
+            items
+                .into_iter()
+                .map(|(k, v)| (HashableValue::String(k.to_string()), v))
+                .collect(),
+        )
+    }
+
+    fn platform() -> Value {
+        dict(vec![
+            (
+                "point",
+                dict(vec![("lat", Value::F64(55.0)), ("lon", Value::F64(37.0))]),
+            ),
+            ("routes", Value::List(vec![Value::I64(0)])),
+        ])
+    }
+
+    fn route(trips: Vec<Value>) -> Value {
+        dict(vec![
+            ("circle", Value::Bool(false)),
+            ("platforms", Value::List(vec![Value::I64(0), Value::I64(0)])),
+            ("trips", Value::List(trips)),
+        ])
+    }
+
+    fn map(routes: Vec<Value>, passages: Value) -> Value {
+        Value::Tuple(vec![
+            Value::List(vec![platform()]),
+            Value::List(routes),
+            passages,
+        ])
+    }
+
+    fn read(value: &Value) -> Result<PublicTransport, String> {
+        PublicTransport::from_value(value, &Location::Root("map")).map_err(|e| e.to_string())
+    }
+
+    fn trip() -> Value {
+        Value::List(vec![Value::I64(10), Value::F64(20.0)])
+    }
+
+    #[test]
+    fn valid() {
+        let map = read(&map(
+            vec![route(vec![trip()])],
+            Value::Dict(BTreeMap::new()),
+        ))
+        .unwrap();
+        assert_eq!(1, map.platforms.len());
+        assert_eq!(&[10, 20], map.routes[0].trips()[0].stops());
+    }
+
+    #[test]
+    fn frequent_trip() {
+        let frequency = Value::Tuple(vec![Value::I64(100), Value::I64(200), Value::I64(30)]);
+        let trip = dict(vec![
+            ("stops", trip()),
+            ("frequencies", Value::List(vec![frequency])),
+        ]);
+        let map = read(&map(vec![route(vec![trip])], Value::Dict(BTreeMap::new()))).unwrap();
+        let trip = &map.routes[0].templates()[0];
+        assert_eq!(&[Frequency::new(100, 200, 30)], trip.frequencies());
+    }
+
+    #[test]
+    fn route_info() {
+        let mut route = route(vec![trip()]);
+        if let Value::Dict(items) = &mut route {
+            for (key, value) in [
+                ("short_name", "7"),
+                ("mode", "tram"),
+                ("headsign", "Central"),
+            ] {
+                items.insert(
+                    HashableValue::String(key.to_string()),
+                    Value::String(value.to_string()),
+                );
+            }
+        }
+        let map = read(&map(vec![route], Value::Dict(BTreeMap::new()))).unwrap();
+        assert_eq!("Tram 7 toward Central", map.routes[0].info.to_string());
+    }
+
+    #[test]
+    fn named_platforms() {
+        let mut platform = platform();
+        if let Value::Dict(items) = &mut platform {
+            for (key, value) in [("name", "Kievskaya"), ("id", "P1"), ("code", "2")] {
+                items.insert(
+                    HashableValue::String(key.to_string()),
+                    Value::String(value.to_string()),
+                );
+            }
+        }
+        let station = dict(vec![
+            ("name", Value::String("Kievskaya".to_string())),
+            ("platforms", Value::List(vec![Value::I64(0)])),
+        ]);
+        let value = Value::Tuple(vec![
+            Value::List(vec![platform]),
+            Value::List(vec![]),
+            Value::Dict(BTreeMap::new()),
+            Value::List(vec![station]),
+        ]);
+        let map = read(&value).unwrap();
+        assert_eq!(Some(0), map.platforms[0].station);
+        assert_eq!(vec![0], map.find_by_id("P1"));
+        assert_eq!(
+            Some("Kievskaya, platform 2".to_string()),
+            map.platform_name(0)
+        );
+        let query = parse_query("Kievskaya, POINT(37.5 55.5)", Some(&map)).unwrap();
+        assert_eq!((Point::new(37.0, 55.0), Point::new(37.5, 55.5)), query);
+        assert!(parse_query("Kievskaya, POINT(37.5 55.5)", None).is_err());
+        assert!(parse_query("Arbatskaya, POINT(37.5 55.5)", Some(&map)).is_err());
+    }
+
+    #[test]
+    fn bad_trip() {
+        let routes = vec![
+            route(vec![trip()]),
+            route(vec![trip(), Value::String("x".to_string())]),
+        ];
+        let error = read(&map(routes, Value::Dict(BTreeMap::new()))).unwrap_err();
+        assert_eq!("routes[1].trips[1]: expected list of numbers", error);
+    }
+
+    #[test]
+    fn missing_field() {
+        let routes = vec![dict(vec![("circle", Value::Bool(true))])];
+        let error = read(&map(routes, Value::Dict(BTreeMap::new()))).unwrap_err();
+        assert_eq!("routes[0].platforms: missing field", error);
+    }
+
+    #[test]
+    fn bad_coordinate() {
+        let platform = dict(vec![
+            (
+                "point",
+                dict(vec![("lat", Value::None), ("lon", Value::F64(37.0))]),
+            ),
+            ("routes", Value::List(vec![])),
+        ]);
+        let value = Value::Tuple(vec![
+            Value::List(vec![platform]),
+            Value::List(vec![]),
+            Value::Dict(BTreeMap::new()),
+        ]);
+        assert_eq!(
+            "platforms[0].point.lat: expected number",
+            read(&value).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn unknown_passage_platform() {
+        let to = BTreeMap::from([(HashableValue::I64(3), Value::F64(60.0))]);
+        let passages = BTreeMap::from([(HashableValue::I64(0), Value::Dict(to))]);
+        let error = read(&map(vec![], Value::Dict(passages))).unwrap_err();
+        assert_eq!("passages[0][3]: unknown platform", error);
+    }
+
+    #[test]
+    fn points() {
+        let (start, finish) = parse_query("POINT(37.5 55.7),POINT(37.8 55.1)", None).unwrap();
+        assert_eq!(37.5, start.x());
+        assert_eq!(55.1, finish.y());
+        assert!(parse_query("POINT(37.5 55.7)", None).is_err());
+        assert!(parse_query("POINT(37.5),POINT(37.8 55.1)", None).is_err());
     }
-    points
 }
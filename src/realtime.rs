@@ -0,0 +1,423 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use chrono::NaiveDate;
+use prost::Message;
+
+use crate::calendar::service_start;
+use crate::map::{OrdinalNumber, PublicTransport, Route, Time, Trip};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delay {
+    pub ordinal: OrdinalNumber,
+    pub time: Time,
+}
+
+impl Delay {
+    pub fn new(ordinal: OrdinalNumber, time: Time) -> Self {
+        Self { ordinal, time }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Decode(prost::DecodeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Decode(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<prost::DecodeError> for Error {
+    fn from(e: prost::DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+const CANCELED: i32 = 3;
+
+// Only the part of GTFS-Realtime needed for trip delays, other fields are skipped
+#[derive(Clone, PartialEq, Message)]
+pub struct FeedMessage {
+    #[prost(message, repeated, tag = "2")]
+    pub entity: Vec<FeedEntity>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FeedEntity {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(bool, optional, tag = "2")]
+    pub is_deleted: Option<bool>,
+    #[prost(message, optional, tag = "3")]
+    pub trip_update: Option<TripUpdate>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TripUpdate {
+    #[prost(message, optional, tag = "1")]
+    pub trip: Option<TripDescriptor>,
+    #[prost(message, repeated, tag = "2")]
+    pub stop_time_update: Vec<StopTimeUpdate>,
+    #[prost(int32, optional, tag = "5")]
+    pub delay: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TripDescriptor {
+    #[prost(string, optional, tag = "1")]
+    pub trip_id: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub start_date: Option<String>,
+    #[prost(int32, optional, tag = "4")]
+    pub schedule_relationship: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StopTimeUpdate {
+    #[prost(uint32, optional, tag = "1")]
+    pub stop_sequence: Option<u32>,
+    #[prost(message, optional, tag = "2")]
+    pub arrival: Option<StopTimeEvent>,
+    #[prost(message, optional, tag = "3")]
+    pub departure: Option<StopTimeEvent>,
+    #[prost(string, optional, tag = "4")]
+    pub stop_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StopTimeEvent {
+    #[prost(int32, optional, tag = "1")]
+    pub delay: Option<i32>,
+    #[prost(int64, optional, tag = "2")]
+    pub time: Option<i64>,
+}
+
+pub fn read_trip_updates(filename: &str) -> Result<FeedMessage, Error> {
+    let data = fs::read(filename)?;
+    Ok(FeedMessage::decode(data.as_slice())?)
+}
+
+fn start_date(update: &TripUpdate) -> Option<NaiveDate> {
+    let date = update.trip.as_ref().and_then(|t| t.start_date.as_ref())?;
+    NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+}
+
+impl PublicTransport {
+    // A trip keeps one set of live times, so only updates for the given service date
+    // are applied, those without a start date are taken to be for it
+    pub fn apply_trip_updates(&mut self, feed: &FeedMessage, date: NaiveDate) -> usize {
+        let mut updated = 0;
+        for entity in feed.entity.iter().filter(|e| !e.is_deleted()) {
+            let Some(update) = &entity.trip_update else {
+                continue;
+            };
+            if start_date(update).is_some_and(|d| d != date) {
+                continue;
+            }
+            let trip = update.trip.as_ref().and_then(|t| t.trip_id.as_ref());
+            let Some(id) = trip.and_then(|t| self.trip_ids.get(t)).copied() else {
+                continue;
+            };
+            let relationship = update.trip.as_ref().and_then(|t| t.schedule_relationship);
+            if relationship == Some(CANCELED) {
+                if self.cancel_trip(id, true) {
+                    updated += 1;
+                }
+                continue;
+            }
+            let Some(delays) = self.delays(id, update, date) else {
+                continue;
+            };
+            if self.delay_trip(id, &delays) {
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    fn delays(&self, id: i32, update: &TripUpdate, date: NaiveDate) -> Option<Vec<Delay>> {
+        let (route, trip) = self.routes.iter().find_map(|r| {
+            let mut trips = r.trips().iter().chain(r.templates());
+            trips.find(|t| t.id() == id).map(|t| (r, t))
+        })?;
+        let mut delays = vec![];
+        if let Some(delay) = update.delay {
+            delays.push(Delay::new(0, delay as Time));
+        }
+        let mut from = 0;
+        for stop in &update.stop_time_update {
+            let Some(ordinal) = self.ordinal(route, trip, stop, from) else {
+                continue;
+            };
+            let event = stop.departure.as_ref().or(stop.arrival.as_ref());
+            let scheduled = trip.scheduled()[ordinal];
+            if let Some(time) = event.and_then(|e| self.event_delay(e, scheduled, date)) {
+                delays.push(Delay::new(ordinal, time));
+                from = ordinal;
+            }
+        }
+        Some(delays)
+    }
+
+    fn ordinal(
+        &self,
+        route: &Route,
+        trip: &Trip,
+        stop: &StopTimeUpdate,
+        from: OrdinalNumber,
+    ) -> Option<OrdinalNumber> {
+        // The sequence tells visits of the same stop apart, so it goes first
+        match (stop.stop_sequence, &stop.stop_id) {
+            (Some(sequence), _) => trip.ordinal_of(sequence),
+            (None, Some(id)) => {
+                let platform = *self.stop_ids.get(id)?;
+                (from..trip.stops().len()).find(|o| route.platform(*o) == platform)
+            }
+            (None, None) => None,
+        }
+    }
+
+    fn event_delay(&self, event: &StopTimeEvent, scheduled: Time, date: NaiveDate) -> Option<Time> {
+        match (event.delay, event.time) {
+            (Some(delay), _) => Some(delay as Time),
+            (None, Some(time)) => {
+                let start = service_start(&self.timezone, date).timestamp();
+                Some(time - start - scheduled)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod updates {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use chrono_tz::Tz;
+
+    use crate::calendar::Day;
+    use crate::map::{Platform, Point};
+
+    fn map() -> PublicTransport {
+        PublicTransport::new(
+            vec![
+                Platform::new(Point::new(0.0, 0.0), vec![0]),
+                Platform::new(Point::new(0.0, 1.0), vec![0]),
+                Platform::new(Point::new(0.0, 2.0), vec![0]),
+            ],
+            vec![Route::new(
+                false,
+                vec![0, 1, 2],
+                vec![
+                    Trip::new(1, vec![100, 200, 300]),
+                    Trip::new(2, vec![400, 500, 600]).with_sequences(vec![10, 20, 40]),
+                ],
+            )],
+            vec![vec![]; 3],
+        )
+        .with_trip_ids(HashMap::from([
+            ("T1".to_string(), 1),
+            ("T2".to_string(), 2),
+        ]))
+        .with_stop_ids(HashMap::from([("B".to_string(), 1)]))
+        .with_timezone(Tz::Europe__Moscow)
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, 10).unwrap()
+    }
+
+    fn stops(map: &PublicTransport) -> Vec<Vec<Time>> {
+        map.routes[0]
+            .trips()
+            .iter()
+            .map(|t| t.stops().to_vec())
+            .collect()
+    }
+
+    fn event(delay: Option<i32>, time: Option<i64>) -> Option<StopTimeEvent> {
+        Some(StopTimeEvent { delay, time })
+    }
+
+    fn feed(trip: &str, start_date: Option<&str>, updates: Vec<StopTimeUpdate>) -> FeedMessage {
+        FeedMessage {
+            entity: vec![FeedEntity {
+                id: Some("1".to_string()),
+                is_deleted: None,
+                trip_update: Some(TripUpdate {
+                    trip: Some(TripDescriptor {
+                        trip_id: Some(trip.to_string()),
+                        start_date: start_date.map(str::to_string),
+                        schedule_relationship: None,
+                    }),
+                    stop_time_update: updates,
+                    delay: None,
+                }),
+            }],
+        }
+    }
+
+    #[test]
+    fn delay_from_stop() {
+        let mut map = map();
+        assert!(map.delay_trip(1, &[Delay::new(1, 60)]));
+        assert_eq!(vec![100, 260, 360], stops(&map)[0]);
+        assert_eq!(&[100, 200, 300], map.routes[0].trips()[0].scheduled());
+    }
+
+    #[test]
+    fn delays_replace_previous() {
+        let mut map = map();
+        map.delay_trip(1, &[Delay::new(0, 60)]);
+        map.delay_trip(1, &[Delay::new(2, 30)]);
+        assert_eq!(vec![100, 200, 330], stops(&map)[0]);
+    }
+
+    #[test]
+    fn no_departure_before_arrival() {
+        let mut map = map();
+        map.delay_trip(1, &[Delay::new(0, 150), Delay::new(1, -100)]);
+        assert_eq!(vec![250, 250, 250], stops(&map)[0]);
+    }
+
+    #[test]
+    fn resort_overtaken() {
+        let mut map = map();
+        map.delay_trip(1, &[Delay::new(0, 400)]);
+        assert_eq!(vec![400, 500, 600], stops(&map)[0]);
+        assert_eq!(1, map.routes[0].trips()[1].id());
+    }
+
+    #[test]
+    fn overtaken_partway() {
+        let mut map = map();
+        map.delay_trip(1, &[Delay::new(1, 350)]);
+        assert_eq!(vec![100, 550, 650], stops(&map)[0]);
+        let route = &map.routes[0];
+        let day = Day::default();
+        let next = route.try_catch(450, 1, None, &day).unwrap();
+        assert_eq!(2, next.id());
+        let previous = route.try_catch_back(520, 1, None, &day).unwrap();
+        assert_eq!(2, previous.id());
+        assert_eq!(1, route.try_catch(50, 0, None, &day).unwrap().id());
+    }
+
+    #[test]
+    fn unknown_trip() {
+        assert!(!map().delay_trip(7, &[Delay::new(0, 60)]));
+    }
+
+    #[test]
+    fn decode_feed() {
+        let update = StopTimeUpdate {
+            stop_sequence: None,
+            arrival: None,
+            departure: event(Some(120), None),
+            stop_id: Some("B".to_string()),
+        };
+        let data = feed("T2", None, vec![update]).encode_to_vec();
+        let mut map = map();
+        let feed = FeedMessage::decode(data.as_slice()).unwrap();
+        assert_eq!(1, map.apply_trip_updates(&feed, today()));
+        assert_eq!(vec![400, 620, 720], stops(&map)[1]);
+    }
+
+    #[test]
+    fn absolute_time() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+        let start = service_start(&Tz::Europe__Moscow, date).timestamp();
+        let update = StopTimeUpdate {
+            stop_sequence: Some(3),
+            arrival: event(None, Some(start + 330)),
+            departure: None,
+            stop_id: None,
+        };
+        let mut map = map();
+        assert_eq!(
+            1,
+            map.apply_trip_updates(&feed("T1", Some("20240110"), vec![update]), date)
+        );
+        assert_eq!(vec![100, 200, 330], stops(&map)[0]);
+    }
+
+    #[test]
+    fn feed_sequence() {
+        let update = StopTimeUpdate {
+            stop_sequence: Some(40),
+            arrival: event(Some(30), None),
+            departure: None,
+            stop_id: None,
+        };
+        let mut map = map();
+        assert_eq!(
+            1,
+            map.apply_trip_updates(&feed("T2", None, vec![update]), today())
+        );
+        assert_eq!(vec![400, 500, 630], stops(&map)[1]);
+        let update = StopTimeUpdate {
+            stop_sequence: Some(3),
+            arrival: event(Some(30), None),
+            departure: None,
+            stop_id: Some("B".to_string()),
+        };
+        map.apply_trip_updates(&feed("T2", None, vec![update]), today());
+        assert_eq!(vec![400, 500, 600], stops(&map)[1]);
+    }
+
+    #[test]
+    fn other_date() {
+        let update = StopTimeUpdate {
+            stop_sequence: None,
+            arrival: event(Some(60), None),
+            departure: None,
+            stop_id: Some("B".to_string()),
+        };
+        let mut map = map();
+        let feed = feed("T1", Some("20240111"), vec![update]);
+        assert_eq!(0, map.apply_trip_updates(&feed, today()));
+        assert_eq!(vec![100, 200, 300], stops(&map)[0]);
+    }
+
+    #[test]
+    fn cancelled() {
+        let mut feed = feed("T2", None, vec![]);
+        let update = feed.entity[0].trip_update.as_mut().unwrap();
+        update.trip.as_mut().unwrap().schedule_relationship = Some(CANCELED);
+        let mut map = map();
+        assert_eq!(1, map.apply_trip_updates(&feed, today()));
+        assert!(map.routes[0].trips()[1].is_cancelled());
+    }
+
+    #[test]
+    fn unknown_trip_id() {
+        let update = StopTimeUpdate {
+            stop_sequence: Some(1),
+            arrival: event(Some(60), None),
+            departure: None,
+            stop_id: None,
+        };
+        let mut map = map();
+        assert_eq!(
+            0,
+            map.apply_trip_updates(&feed("T9", None, vec![update]), today())
+        );
+    }
+}
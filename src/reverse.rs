@@ -0,0 +1,239 @@
+use std::cmp;
+use std::rc::Rc;
+
+use chrono::NaiveDate;
+
+use crate::calendar::Day;
+use crate::map::{Passage, PlatformIndex, PublicTransport, Time};
+use crate::path::Path;
+use crate::platforms::Platforms;
+use crate::searcher::{make_parts, Marked, Routes, Stop, Vehicle, Way};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Label {
+    departure: Time,
+    arrival: Time,
+    way: Option<Way>,
+}
+
+type Labels = Vec<Label>;
+
+impl Label {
+    fn new(departure: Time, arrival: Time, way: Option<Way>) -> Self {
+        Self {
+            departure,
+            arrival,
+            way,
+        }
+    }
+
+    fn infinity() -> Self {
+        Self {
+            departure: Time::MIN,
+            arrival: Time::MIN,
+            way: None,
+        }
+    }
+}
+
+pub struct ReverseSearcher<'a> {
+    map: &'a PublicTransport,
+    platforms: Platforms,
+    passages: Vec<Vec<Passage>>,
+    days: Rc<[Day]>,
+    departure: Time,
+    best: Labels,
+    labels: Vec<Labels>,
+}
+
+impl<'a> ReverseSearcher<'a> {
+    pub fn new(map: &'a PublicTransport, platforms: Platforms) -> Self {
+        let mut passages = vec![vec![]; map.platforms.len()];
+        for (from, outgoing) in map.passages.iter().enumerate() {
+            for passage in outgoing {
+                passages[passage.to].push(Passage::new(from, passage.time));
+            }
+        }
+        Self {
+            map,
+            platforms,
+            passages,
+            days: Rc::from([Day::default()]),
+            departure: Time::MIN,
+            best: vec![Label::infinity(); map.platforms.len()],
+            labels: vec![],
+        }
+    }
+
+    pub fn with_date(mut self, date: NaiveDate) -> Self {
+        self.days = Day::around(&self.map.services, &self.map.timezone, date);
+        self
+    }
+
+    pub fn ready(&self) -> bool {
+        !self.platforms.from.is_empty() && !self.platforms.to.is_empty()
+    }
+
+    pub fn run(&mut self, arrival: Time) -> Vec<Path> {
+        let mut marked = self.init(arrival);
+        while !marked.is_empty() {
+            self.round();
+            let routes = self.accumulate(marked);
+            marked = self.traverse(routes);
+            marked.extend(self.transfer(&marked));
+        }
+        self.paths()
+    }
+
+    fn round(&mut self) {
+        self.labels.push(self.labels.last().unwrap().clone());
+    }
+
+    fn init(&mut self, arrival: Time) -> Marked {
+        let mut marked = Marked::new();
+        for (platform, duration) in &self.platforms.to {
+            if !self.map.is_open(*platform, arrival - duration) {
+                continue;
+            }
+            self.best[*platform] = Label::new(arrival - duration, arrival - duration, None);
+            marked.insert(*platform);
+        }
+        self.labels = vec![self.best.clone()];
+        marked
+    }
+
+    fn accumulate(&self, marked: Marked) -> Routes {
+        let mut routes = Routes::new();
+        for mp in marked {
+            for r in &self.map.platforms[mp].routes {
+                let route = &self.map.routes[*r];
+                let op = routes.get(r);
+                if op.is_none() || route.is_after(&mp, op.unwrap()) {
+                    routes.insert(*r, mp);
+                }
+            }
+        }
+        routes
+    }
+
+    fn traverse(&mut self, routes: Routes) -> Marked {
+        let round = self.labels.len() - 1;
+        let mut marked = Marked::new();
+        for (r, p) in routes {
+            let route = &self.map.routes[r];
+            let mut vehicle = Vehicle::new(self.map, r, self.days.clone());
+            for ordinal in route.head(&p).rev() {
+                let platform = route.platform(ordinal);
+                if vehicle.on_way() {
+                    let departure = vehicle.arrival(ordinal);
+                    // With local and target pruning
+                    let local = cmp::max(
+                        self.best[platform].departure,
+                        self.labels[round][platform].departure,
+                    );
+                    let maximal = cmp::max(local, self.departure);
+                    if departure > maximal && self.map.is_open(platform, departure) {
+                        let arrival = vehicle.boarded();
+                        let way = vehicle.way_back(ordinal);
+                        self.best[platform] = Label::new(departure, arrival, Some(way));
+                        self.labels[round][platform] = self.best[platform].clone();
+                        marked.insert(platform);
+                        self.update(&platform, departure);
+                    }
+                }
+                vehicle.update_back(self.alighting(platform), ordinal);
+            }
+        }
+        marked
+    }
+
+    fn transfer(&mut self, marked: &Marked) -> Marked {
+        let round = self.labels.len() - 1;
+        let labels = &mut self.labels[round];
+        let mut also_marked = Marked::new();
+        for to in marked {
+            for passage in &self.passages[*to] {
+                let maximal = labels[passage.to].departure;
+                let departure = labels[*to].departure - passage.time;
+                let open = self.map.is_open(*to, labels[*to].departure)
+                    && self.map.is_open(passage.to, departure);
+                if departure > maximal && open {
+                    let from = Stop::new(passage.to, None);
+                    let arrival = labels[*to].departure;
+                    let way =
+                        Way::new(from, Stop::new(*to, None), None).with_times(departure, arrival);
+                    self.best[passage.to] = Label::new(departure, arrival, Some(way));
+                    labels[passage.to] = self.best[passage.to].clone();
+                    also_marked.insert(passage.to);
+                }
+            }
+        }
+        also_marked
+    }
+
+    fn paths(&self) -> Vec<Path> {
+        let mut paths: Vec<Path> = Vec::new();
+        for (k, labels) in self.labels.iter().enumerate() {
+            for (platform, duration) in &self.platforms.from {
+                if k > 0 && labels[*platform] == self.labels[k - 1][*platform] {
+                    continue;
+                }
+                if let Some(p) = self.unwind(labels, *platform, *duration) {
+                    paths.push(p);
+                }
+            }
+        }
+        paths
+    }
+
+    fn unwind(&self, labels: &Labels, platform: PlatformIndex, duration: Time) -> Option<Path> {
+        if !by_route(labels, platform) {
+            return None;
+        }
+        let mut ways: Vec<&Way> = vec![];
+        let mut walking = duration;
+        let mut label = &labels[platform];
+        let mut arrival = label.arrival;
+        let mut to = platform;
+        while let Some(way) = &label.way {
+            ways.push(way);
+            if way.route.is_none() {
+                walking += label.arrival - label.departure;
+            }
+            arrival = label.arrival;
+            to = way.to.platform;
+            label = &labels[to];
+        }
+        let egress = self.platforms.to.get(&to)?;
+        let departure = labels[platform].departure - duration;
+        let parts = make_parts(self.map, &ways, labels[platform].departure);
+        Some(Path::new(parts, departure, arrival + egress).with_walking(walking + egress))
+    }
+
+    // The last alighting before walking to the destination needs no change time
+    fn alighting(&self, platform: PlatformIndex) -> Time {
+        let label = &self.best[platform];
+        let changes = &self.map.changes;
+        match &label.way {
+            Some(way) if way.route.is_some() => label.departure - changes.time(platform, platform),
+            Some(way) => {
+                let change = label.arrival - changes.time(platform, way.to.platform);
+                cmp::min(label.departure, change)
+            }
+            None => label.departure,
+        }
+    }
+
+    fn update(&mut self, platform: &PlatformIndex, departure: Time) {
+        if let Some(duration) = self.platforms.from.get(platform) {
+            self.departure = cmp::max(departure - duration, self.departure);
+        }
+    }
+}
+
+fn by_route(labels: &Labels, platform: PlatformIndex) -> bool {
+    match &labels[platform].way {
+        Some(way) => way.route.is_some(),
+        None => false,
+    }
+}
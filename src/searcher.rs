@@ -1,45 +1,65 @@
 use std::cmp::{self, Eq};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::rc::Rc;
 
+use chrono::NaiveDate;
 use geo_types::Coord;
 
+use crate::calendar::Day;
 use crate::map::{
-    OrdinalNumber, PlatformIndex, Point, PublicTransport, Route, RouteIndex, Time, Trip,
+    Departure, OrdinalNumber, PlatformIndex, Point, PublicTransport, Route, RouteIndex, Time,
 };
-use crate::path::{Part, Path};
+use crate::options::Options;
+use crate::path::{pareto, Part, Path};
 use crate::platforms::Platforms;
 
-type Marked = HashSet<PlatformIndex>;
-type Routes = HashMap<RouteIndex, PlatformIndex>;
+pub(crate) type Marked = HashSet<PlatformIndex>;
+pub(crate) type Routes = HashMap<RouteIndex, PlatformIndex>;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-struct Stop {
-    platform: PlatformIndex,
-    ordinal: Option<OrdinalNumber>,
+pub(crate) struct Stop {
+    pub(crate) platform: PlatformIndex,
+    pub(crate) ordinal: Option<OrdinalNumber>,
 }
 
 impl Stop {
-    fn new(platform: PlatformIndex, ordinal: Option<OrdinalNumber>) -> Self {
+    pub(crate) fn new(platform: PlatformIndex, ordinal: Option<OrdinalNumber>) -> Self {
         Self { platform, ordinal }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-struct Way {
-    from: Stop,
-    to: Stop,
-    route: Option<RouteIndex>,
+pub(crate) struct Way {
+    pub(crate) from: Stop,
+    pub(crate) to: Stop,
+    pub(crate) route: Option<RouteIndex>,
+    pub(crate) departure: Time,
+    pub(crate) arrival: Time,
 }
 
 impl Way {
-    fn new(from: Stop, to: Stop, route: Option<RouteIndex>) -> Self {
-        Self { from, to, route }
+    pub(crate) fn new(from: Stop, to: Stop, route: Option<RouteIndex>) -> Self {
+        Self {
+            from,
+            to,
+            route,
+            departure: 0,
+            arrival: 0,
+        }
+    }
+
+    pub(crate) fn with_times(mut self, departure: Time, arrival: Time) -> Self {
+        self.departure = departure;
+        self.arrival = arrival;
+        self
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Label {
     arrival: Time,
+    walking: Time,
     way: Option<Way>,
 }
 
@@ -47,103 +67,274 @@ type Labels = Vec<Label>;
 
 impl Label {
     fn new(arrival: Time, way: Option<Way>) -> Self {
-        Self { arrival, way }
+        Self {
+            arrival,
+            walking: 0,
+            way,
+        }
+    }
+
+    fn with_walking(mut self, walking: Time) -> Self {
+        self.walking = walking;
+        self
     }
 
     fn infinity() -> Self {
         Self {
             arrival: Time::MAX,
+            walking: 0,
             way: None,
         }
     }
 }
 
-struct Vehicle<'a> {
+pub(crate) struct Vehicle<'a> {
+    map: &'a PublicTransport,
     index: RouteIndex,
     route: &'a Route,
-    trip: Option<&'a Trip>,
+    days: Rc<[Day]>,
+    trip: Option<Departure<'a>>,
+    offset: Time,
     from: Option<OrdinalNumber>,
+    boarded: Time,
 }
 
 impl<'a> Vehicle<'a> {
-    fn new(index: RouteIndex, route: &'a Route) -> Self {
+    pub(crate) fn new(map: &'a PublicTransport, index: RouteIndex, days: Rc<[Day]>) -> Self {
         Self {
+            map,
             index,
-            route,
+            route: &map.routes[index],
+            days,
             from: None,
             trip: None,
+            offset: 0,
+            boarded: Time::MAX,
         }
     }
 
-    fn on_way(&self) -> bool {
+    pub(crate) fn on_way(&self) -> bool {
         self.trip.is_some() && self.from.is_some()
     }
 
-    fn arrival(&self, ordinal: OrdinalNumber) -> Time {
-        self.trip.unwrap().stop(ordinal, self.route.circle)
+    pub(crate) fn arrival(&self, ordinal: OrdinalNumber) -> Time {
+        self.trip.unwrap().stop(ordinal, self.route.circle) + self.offset
+    }
+
+    pub(crate) fn boards_at(&self, ordinal: OrdinalNumber) -> bool {
+        self.from == Some(ordinal)
+    }
+
+    pub(crate) fn boarded(&self) -> Time {
+        self.boarded
     }
 
     fn make_stop(&self, ordinal: OrdinalNumber) -> Stop {
         Stop::new(self.route.platform(ordinal), Some(ordinal))
     }
 
-    fn way(&self, to: OrdinalNumber) -> Way {
+    pub(crate) fn way(&self, to: OrdinalNumber) -> Way {
         Way::new(
             self.make_stop(self.from.unwrap()),
             self.make_stop(to),
             Some(self.index),
         )
+        .with_times(self.boarded, self.arrival(to))
+    }
+
+    pub(crate) fn way_back(&self, to: OrdinalNumber) -> Way {
+        Way::new(
+            self.make_stop(to),
+            self.make_stop(self.from.unwrap()),
+            Some(self.index),
+        )
+        .with_times(self.arrival(to), self.boarded)
+    }
+
+    fn is_open(&self, trip: Departure, ordinal: OrdinalNumber, day: &Day) -> bool {
+        let time = trip.stop(ordinal, self.route.circle) + day.offset();
+        self.map.is_open(self.route.platform(ordinal), time)
+    }
+
+    // The vehicle's own time at a closed platform rules the trip out, so the next one is
+    // tried, while staying aboard over the seam of a circle needs no stop
+    fn catch(
+        &self,
+        time: Time,
+        ordinal: OrdinalNumber,
+        current: Option<Departure<'a>>,
+        day: &Day,
+    ) -> Option<Departure<'a>> {
+        let mut time = time;
+        let mut closed = vec![];
+        loop {
+            let trip = self
+                .route
+                .try_catch_skipping(time, ordinal, current, day, &closed)?;
+            let stays = current.is_some_and(|current| {
+                self.route.is_seam(ordinal) && is_same_vehicle(current, trip)
+            });
+            if stays || self.is_open(trip, ordinal, day) {
+                return Some(trip);
+            }
+            time = trip.stop(ordinal, self.route.circle);
+            closed.push(trip);
+        }
+    }
+
+    fn catch_back(
+        &self,
+        time: Time,
+        ordinal: OrdinalNumber,
+        current: Option<Departure<'a>>,
+        day: &Day,
+    ) -> Option<Departure<'a>> {
+        let mut time = time;
+        let mut closed = vec![];
+        loop {
+            let trip = self
+                .route
+                .try_catch_back_skipping(time, ordinal, current, day, &closed)?;
+            let stays = current.is_some_and(|current| {
+                self.route.is_reverse_seam(ordinal) && is_same_vehicle(trip, current)
+            });
+            if stays || self.is_open(trip, ordinal, day) {
+                return Some(trip);
+            }
+            time = trip.stop(ordinal, self.route.circle);
+            closed.push(trip);
+        }
+    }
+
+    pub(crate) fn update_back(&mut self, time: Time, ordinal: OrdinalNumber) {
+        let days = self.days.clone();
+        for day in days.iter() {
+            let current = self.trip.filter(|_| self.offset == day.offset());
+            let time = time.saturating_sub(day.offset());
+            let Some(previous_trip) = self.catch_back(time, ordinal, current, day) else {
+                continue;
+            };
+            match current {
+                Some(current_trip) if current_trip == previous_trip => (),
+                Some(current_trip)
+                    if self.route.is_reverse_seam(ordinal)
+                        && is_same_vehicle(previous_trip, current_trip) =>
+                {
+                    self.trip = Some(previous_trip)
+                }
+                Some(_) => self.board(previous_trip, day.offset(), ordinal),
+                None => {
+                    let departure = previous_trip.stop(ordinal, self.route.circle) + day.offset();
+                    if self.trip.is_none() || departure > self.arrival(ordinal) {
+                        self.board(previous_trip, day.offset(), ordinal);
+                    }
+                }
+            };
+        }
     }
 
-    fn update(&mut self, time: Time, ordinal: OrdinalNumber) {
-        let trip = self.route.try_catch(time, ordinal, self.trip);
-        if let Some(next_trip) = trip {
-            match self.trip {
+    pub(crate) fn update(&mut self, time: Time, ordinal: OrdinalNumber) {
+        let days = self.days.clone();
+        for day in days.iter() {
+            let current = self.trip.filter(|_| self.offset == day.offset());
+            let time = time.saturating_sub(day.offset());
+            let Some(next_trip) = self.catch(time, ordinal, current, day) else {
+                continue;
+            };
+            match current {
                 Some(current_trip) if current_trip == next_trip => (),
                 Some(current_trip)
-                    if self.route.is_seam(ordinal) && is_same_vehicle(&current_trip, next_trip) =>
+                    if self.route.is_seam(ordinal) && is_same_vehicle(current_trip, next_trip) =>
                 {
                     self.trip = Some(next_trip)
                 }
-                _ => {
-                    self.from = Some(ordinal);
-                    self.trip = Some(next_trip);
+                Some(current_trip) => {
+                    let departure = self.seam_departure(time, ordinal, Some(current_trip), day);
+                    self.board(next_trip, day.offset(), ordinal);
+                    self.boarded = departure.unwrap_or(self.boarded);
+                }
+                None => {
+                    let arrival = next_trip.stop(ordinal, self.route.circle) + day.offset();
+                    if self.trip.is_none() || arrival < self.arrival(ordinal) {
+                        let departure = self.seam_departure(time, ordinal, None, day);
+                        self.board(next_trip, day.offset(), ordinal);
+                        self.boarded = departure.unwrap_or(self.boarded);
+                    }
                 }
             };
         }
     }
+
+    // Past the seam of a circle the next trip takes over, but the seam itself is left by
+    // the trip before it
+    fn seam_departure(
+        &self,
+        time: Time,
+        ordinal: OrdinalNumber,
+        current: Option<Departure>,
+        day: &Day,
+    ) -> Option<Time> {
+        if !self.route.is_seam(ordinal) {
+            return None;
+        }
+        let leaving = match current {
+            Some(trip) => trip,
+            None => self.route.leaving_seam(time, day)?,
+        };
+        Some(leaving.stop(ordinal, self.route.circle) + day.offset())
+    }
+
+    fn board(&mut self, trip: Departure<'a>, offset: Time, ordinal: OrdinalNumber) {
+        self.from = Some(ordinal);
+        self.trip = Some(trip);
+        self.offset = offset;
+        self.boarded = self.arrival(ordinal);
+    }
 }
 
-fn is_same_vehicle(current_trip: &Trip, next_trip: &Trip) -> bool {
+fn is_same_vehicle(current_trip: Departure, next_trip: Departure) -> bool {
     current_trip.last() == next_trip.first()
 }
 
 pub struct Searcher<'a> {
     map: &'a PublicTransport,
     platforms: Platforms,
+    options: Options,
+    days: Rc<[Day]>,
+    departure: Time,
+    latest: Time,
     arrival: Time,
     best: Labels,
     labels: Vec<Labels>,
 }
 
 impl<'a> Searcher<'a> {
-    pub fn new(map: &'a PublicTransport, platforms: Platforms) -> Self {
+    pub fn new(map: &'a PublicTransport, platforms: Platforms, options: Options) -> Self {
         Self {
             map,
             platforms,
+            options,
+            days: Rc::from([Day::default()]),
+            departure: 0,
+            latest: Time::MAX,
             arrival: Time::MAX,
             best: vec![Label::infinity(); map.platforms.len()],
             labels: vec![],
         }
     }
 
+    pub fn with_date(mut self, date: NaiveDate) -> Self {
+        self.days = Day::around(&self.map.services, &self.map.timezone, date);
+        self
+    }
+
     pub fn ready(&self) -> bool {
         !self.platforms.from.is_empty() && !self.platforms.to.is_empty()
     }
 
     pub fn run(&mut self, departure: Time) -> Vec<Path> {
         let mut marked = self.init(departure);
-        while !marked.is_empty() {
+        while !marked.is_empty() && self.options.has_round(self.labels.len()) {
             self.round();
             let routes = self.accumulate(marked);
             marked = self.traverse(routes);
@@ -152,14 +343,131 @@ impl<'a> Searcher<'a> {
         self.paths()
     }
 
+    pub fn profile(&mut self, window: Range<Time>) -> Vec<Path> {
+        let mut paths = vec![];
+        let mut previous = vec![vec![Label::infinity(); self.map.platforms.len()]];
+        for departure in self.departures(window).into_iter().rev() {
+            let mut marked = self.restart(departure, &previous);
+            while !marked.is_empty() && self.options.has_round(self.labels.len()) {
+                self.next_round(&previous);
+                let routes = self.accumulate(marked);
+                marked = self.traverse(routes);
+                marked.extend(self.transfer(&marked));
+            }
+            paths.extend(self.improved(&previous));
+            self.keep(&mut previous);
+        }
+        pareto(paths)
+    }
+
     fn round(&mut self) {
         self.labels.push(self.labels.last().unwrap().clone());
     }
 
+    fn departures(&self, window: Range<Time>) -> BTreeSet<Time> {
+        let mut departures = BTreeSet::new();
+        for (platform, duration) in &self.platforms.from {
+            for r in &self.map.platforms[*platform].routes {
+                let route = &self.map.routes[*r];
+                for (ordinal, p) in route.platforms().iter().enumerate() {
+                    if p != platform {
+                        continue;
+                    }
+                    for day in self.days.iter() {
+                        let shift = day.offset() - duration;
+                        let range = (window.start - shift)..(window.end - shift);
+                        let times = route.times_at(ordinal, day, range);
+                        departures.extend(times.into_iter().map(|t| t + shift));
+                    }
+                }
+            }
+        }
+        departures
+    }
+
+    // Labels of later departures stay valid, so each run starts from them
+    fn restart(&mut self, departure: Time, previous: &[Labels]) -> Marked {
+        self.departure = departure;
+        self.latest = self.options.latest(departure);
+        let mut labels = previous[0].clone();
+        let mut marked = Marked::new();
+        for (platform, duration) in &self.platforms.from {
+            let arrival = departure + duration;
+            if arrival < labels[*platform].arrival
+                && self.options.can_walk(*duration)
+                && self.map.is_open(*platform, arrival)
+            {
+                labels[*platform] = Label::new(arrival, None).with_walking(*duration);
+                marked.insert(*platform);
+            }
+        }
+        self.labels = vec![labels];
+        marked
+    }
+
+    fn next_round(&mut self, previous: &[Labels]) {
+        let last = self.labels.last().unwrap();
+        let mut labels = match previous.get(self.labels.len()) {
+            Some(labels) => labels.clone(),
+            None => last.clone(),
+        };
+        merge(&mut labels, last);
+        self.best = last.clone();
+        self.arrival = self
+            .platforms
+            .to
+            .iter()
+            .filter(|(platform, duration)| {
+                self.options
+                    .can_walk(labels[**platform].walking + **duration)
+            })
+            .map(|(platform, duration)| labels[*platform].arrival.saturating_add(*duration))
+            .filter(|arrival| *arrival <= self.latest)
+            .min()
+            .unwrap_or(Time::MAX);
+        self.labels.push(labels);
+    }
+
+    fn improved(&self, previous: &[Labels]) -> Vec<Path> {
+        let mut paths = vec![];
+        for (k, labels) in self.labels.iter().enumerate().skip(1) {
+            for (platform, duration) in &self.platforms.to {
+                let label = &labels[*platform];
+                let before = previous.get(k).map_or(Time::MAX, |l| l[*platform].arrival);
+                if label.arrival >= before || *label == self.labels[k - 1][*platform] {
+                    continue;
+                }
+                if let Some(p) = self.unwind(labels, *platform, *duration) {
+                    paths.push(p);
+                }
+            }
+        }
+        paths
+    }
+
+    fn keep(&self, previous: &mut Vec<Labels>) {
+        let last = self.labels.last().unwrap();
+        for labels in previous.iter_mut().skip(self.labels.len()) {
+            merge(labels, last);
+        }
+        for (k, labels) in self.labels.iter().enumerate() {
+            match previous.get_mut(k) {
+                Some(kept) => *kept = labels.clone(),
+                None => previous.push(labels.clone()),
+            }
+        }
+    }
+
     fn init(&mut self, departure: Time) -> Marked {
+        self.departure = departure;
+        self.latest = self.options.latest(departure);
         let mut marked = Marked::new();
         for (platform, duration) in &self.platforms.from {
-            self.best[*platform] = Label::new(departure + duration, None);
+            let arrival = departure + duration;
+            if !self.options.can_walk(*duration) || !self.map.is_open(*platform, arrival) {
+                continue;
+            }
+            self.best[*platform] = Label::new(arrival, None).with_walking(*duration);
             marked.insert(*platform);
         }
         self.labels = vec![self.best.clone()];
@@ -167,17 +475,7 @@ impl<'a> Searcher<'a> {
     }
 
     fn accumulate(&self, marked: Marked) -> Routes {
-        let mut routes = Routes::new();
-        for mp in marked {
-            for r in &self.map.platforms[mp].routes {
-                let route = &self.map.routes[*r];
-                let op = routes.get(r);
-                if op.is_none() || route.is_before(&mp, op.unwrap()) {
-                    routes.insert(*r, mp);
-                }
-            }
-        }
-        routes
+        accumulate(self.map, marked)
     }
 
     fn traverse(&mut self, routes: Routes) -> Marked {
@@ -185,22 +483,33 @@ impl<'a> Searcher<'a> {
         let mut marked = Marked::new();
         for (r, p) in routes {
             let route = &self.map.routes[r];
-            let mut vehicle = Vehicle::new(r, route);
+            let mut vehicle = Vehicle::new(self.map, r, self.days.clone());
+            let mut walking = 0;
             for ordinal in route.tail(&p) {
                 let platform = route.platform(ordinal);
                 if vehicle.on_way() {
                     let arrival = vehicle.arrival(ordinal);
                     // With local and target pruning
-                    let minimal = cmp::min(self.best[platform].arrival, self.arrival);
-                    if arrival < minimal {
-                        self.best[platform] = Label::new(arrival, Some(vehicle.way(ordinal)));
+                    let local = cmp::min(
+                        self.best[platform].arrival,
+                        self.labels[round][platform].arrival,
+                    );
+                    let minimal = cmp::min(local, self.arrival);
+                    if arrival < minimal
+                        && arrival <= self.latest
+                        && self.map.is_open(platform, arrival)
+                    {
+                        let way = Some(vehicle.way(ordinal));
+                        self.best[platform] = Label::new(arrival, way).with_walking(walking);
                         self.labels[round][platform] = self.best[platform].clone();
                         marked.insert(platform);
-                        self.update(&platform, arrival);
+                        self.update(&platform, arrival, walking);
                     }
                 }
-                let arrival = self.best[platform].arrival;
-                vehicle.update(arrival, ordinal);
+                vehicle.update(self.boarding(platform), ordinal);
+                if vehicle.boards_at(ordinal) {
+                    walking = self.best[platform].walking;
+                }
             }
         }
         marked
@@ -214,11 +523,18 @@ impl<'a> Searcher<'a> {
             for passage in &self.map.passages[*from] {
                 let minimal = labels[passage.to].arrival;
                 let arrival = labels[*from].arrival + passage.time;
-                if arrival < minimal {
+                let walking = labels[*from].walking + passage.time;
+                if arrival < minimal
+                    && arrival <= self.latest
+                    && self.options.can_walk(walking)
+                    && self.map.is_open(*from, labels[*from].arrival)
+                    && self.map.is_open(passage.to, arrival)
+                {
+                    let departure = labels[*from].arrival;
                     let from = Stop::new(*from, None);
                     let to = Stop::new(passage.to, None);
-                    let way = Way::new(from, to, None);
-                    self.best[passage.to] = Label::new(arrival, Some(way));
+                    let way = Way::new(from, to, None).with_times(departure, arrival);
+                    self.best[passage.to] = Label::new(arrival, Some(way)).with_walking(walking);
                     labels[passage.to] = self.best[passage.to].clone();
                     also_marked.insert(passage.to);
                 }
@@ -246,41 +562,54 @@ impl<'a> Searcher<'a> {
         if on_foot(labels, platform) {
             return None;
         }
-        let mut parts: Vec<Part> = vec![];
+        let mut ways: Vec<&Way> = vec![];
+        let mut walking = duration;
         let mut way = labels[platform].way.as_ref();
         let mut from: Option<&PlatformIndex> = None;
         while way.is_some() {
             let w = way.unwrap();
-            parts.push(self.make_part(w));
+            ways.push(w);
+            if w.route.is_none() {
+                walking += passage_time(self.map, w);
+            }
             from = Some(&w.from.platform);
             way = labels[w.from.platform].way.as_ref();
         }
         if !self.is_from(from) {
             return None;
         }
+        let access = self.platforms.from[from.unwrap()];
+        walking += access;
         let arrival = labels[platform].arrival + duration;
-        parts.reverse();
-        Some(Path::new(parts, arrival))
-    }
-
-    fn make_part(&self, way: &Way) -> Part {
-        let mut points = vec![];
-        if way.route.is_some() {
-            let route = &self.map.routes[way.route.unwrap()];
-            let range = route.range(way.from.ordinal.unwrap(), way.to.ordinal.unwrap());
-            for p in range {
-                points.push(make_point(&self.map.platforms[*p].point));
+        if arrival > self.latest || !self.options.can_walk(walking) {
+            return None;
+        }
+        ways.reverse();
+        let parts = make_parts(self.map, &ways, self.departure + access);
+        Some(Path::new(parts, self.departure, arrival).with_walking(walking))
+    }
+
+    // The first boarding after walking from the origin needs no change time
+    fn boarding(&self, platform: PlatformIndex) -> Time {
+        let label = &self.best[platform];
+        let changes = &self.map.changes;
+        match &label.way {
+            Some(way) if way.route.is_some() => label.arrival + changes.time(platform, platform),
+            Some(way) => {
+                let alighting = label.arrival - passage_time(self.map, way);
+                let change = alighting + changes.time(way.from.platform, platform);
+                cmp::max(label.arrival, change)
             }
-        } else {
-            points.push(make_point(&self.map.platforms[way.from.platform].point));
-            points.push(make_point(&self.map.platforms[way.to.platform].point));
+            None => label.arrival,
         }
-        Part::new(points, way.route)
     }
 
-    fn update(&mut self, platform: &PlatformIndex, arrival: Time) {
+    fn update(&mut self, platform: &PlatformIndex, arrival: Time, walking: Time) {
         if let Some(duration) = self.platforms.to.get(platform) {
-            self.arrival = cmp::min(arrival + duration, self.arrival);
+            let arrival = arrival + duration;
+            if arrival <= self.latest && self.options.can_walk(walking + duration) {
+                self.arrival = cmp::min(arrival, self.arrival);
+            }
         }
     }
 
@@ -289,6 +618,68 @@ impl<'a> Searcher<'a> {
     }
 }
 
+pub(crate) fn accumulate(map: &PublicTransport, marked: Marked) -> Routes {
+    let mut routes = Routes::new();
+    for mp in marked {
+        for r in &map.platforms[mp].routes {
+            let route = &map.routes[*r];
+            let op = routes.get(r);
+            if op.is_none() || route.is_before(&mp, op.unwrap()) {
+                routes.insert(*r, mp);
+            }
+        }
+    }
+    routes
+}
+
+fn make_part(map: &PublicTransport, way: &Way) -> Part {
+    let mut points = vec![];
+    if let Some(route) = way.route {
+        let route = &map.routes[route];
+        let range = route.range(way.from.ordinal.unwrap(), way.to.ordinal.unwrap());
+        for p in range {
+            points.push(make_point(&map.platforms[*p].point));
+        }
+    } else {
+        points.push(make_point(&map.platforms[way.from.platform].point));
+        points.push(make_point(&map.platforms[way.to.platform].point));
+    }
+    let walking = match way.route {
+        Some(_) => 0,
+        None => way.arrival - way.departure,
+    };
+    Part::new(points, way.route)
+        .with_platforms(Some(way.from.platform), Some(way.to.platform))
+        .with_times(way.departure, way.arrival)
+        .with_walking(walking)
+}
+
+// Ways go from the origin, which is reached at the start time
+pub(crate) fn make_parts(map: &PublicTransport, ways: &[&Way], start: Time) -> Vec<Part> {
+    let mut time = start;
+    let mut parts = vec![];
+    for way in ways {
+        parts.push(make_part(map, way).with_waiting(way.departure - time));
+        time = way.arrival;
+    }
+    parts
+}
+
+pub(crate) fn passage_time(map: &PublicTransport, way: &Way) -> Time {
+    map.passages[way.from.platform]
+        .iter()
+        .find(|p| p.to == way.to.platform)
+        .map_or(0, |p| p.time)
+}
+
+fn merge(labels: &mut Labels, other: &Labels) {
+    for (label, other) in labels.iter_mut().zip(other) {
+        if other.arrival < label.arrival {
+            *label = other.clone();
+        }
+    }
+}
+
 fn make_point(point: &Point) -> Coord<f64> {
     Coord {
         x: point.lon,
@@ -442,11 +833,12 @@ mod labels {
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod searcher {
     use super::*;
 
     use crate::{
-        map::{Passage, Platform},
+        map::{Passage, Platform, Trip},
         platforms::Platforms,
     };
 
@@ -511,7 +903,7 @@ mod searcher {
     fn accumulate_routes() {
         let map = PublicTransport::new(platforms(), routes(), passages());
         let platforms = Platforms::default();
-        let searcher = Searcher::new(&map, platforms);
+        let searcher = Searcher::new(&map, platforms, Options::default());
         let marked = Marked::from([11, 5, 2, 14]);
 
         let expected = Routes::from([(0, 2), (1, 11)]);
@@ -522,7 +914,7 @@ mod searcher {
     fn accumulate_loop_routes() {
         let map = PublicTransport::new(platforms(), loop_routes(), passages());
         let platforms = Platforms::default();
-        let searcher = Searcher::new(&map, platforms);
+        let searcher = Searcher::new(&map, platforms, Options::default());
         let marked = Marked::from([3, 4, 15, 16]);
 
         let expected = Routes::from([(0, 3), (1, 15)]);
@@ -533,7 +925,7 @@ mod searcher {
     fn do_transfer() {
         let map = PublicTransport::new(platforms(), routes(), passages());
         let platforms = Platforms::default();
-        let mut searcher = Searcher::new(&map, platforms);
+        let mut searcher = Searcher::new(&map, platforms, Options::default());
         let marked = Marked::from([2, 15]);
 
         let expected = Marked::from([0, 7, 10, 19]);
@@ -547,7 +939,7 @@ mod searcher {
     fn do_traverse() {
         let map = PublicTransport::new(platforms(), routes(), passages());
         let platforms = Platforms::default();
-        let mut searcher = Searcher::new(&map, platforms);
+        let mut searcher = Searcher::new(&map, platforms, Options::default());
         let routes = Routes::from([(0, 2), (1, 11)]);
         searcher.best = vec![Label::infinity(); 20];
         searcher.best[2].arrival = 10;
@@ -566,7 +958,7 @@ mod searcher {
     fn do_loop_traverse() {
         let map = PublicTransport::new(platforms(), loop_routes(), passages());
         let platforms = Platforms::default();
-        let mut searcher = Searcher::new(&map, platforms);
+        let mut searcher = Searcher::new(&map, platforms, Options::default());
         let routes = Routes::from([(0, 3), (1, 15)]);
         searcher.best = vec![Label::new(1, None); 20];
         searcher.best[4] = Label::infinity();
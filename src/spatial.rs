@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use geo_types::Point;
+
+use crate::map::{Platform, PlatformIndex};
+
+const CELL: f64 = 500.0;
+
+type Cell = (i64, i64);
+
+#[derive(Debug, Default)]
+pub struct Grid {
+    zone: u8,
+    points: Vec<Point<f64>>,
+    cells: HashMap<Cell, Vec<PlatformIndex>>,
+    min: Cell,
+    max: Cell,
+}
+
+impl Grid {
+    pub fn new(platforms: &[Platform]) -> Self {
+        let points: Vec<_> = platforms
+            .iter()
+            .map(|p| Point::new(p.point.lon, p.point.lat))
+            .collect();
+        Self::from_points(&points)
+    }
+
+    pub fn from_points(points: &[Point<f64>]) -> Self {
+        if points.is_empty() {
+            return Self::default();
+        }
+        let count = points.len() as f64;
+        let lat = points.iter().map(|p| p.y()).sum::<f64>() / count;
+        let lon = points.iter().map(|p| p.x()).sum::<f64>() / count;
+        let zone = utm::lat_lon_to_zone_number(lat, lon);
+        let points: Vec<_> = points.iter().map(|p| project(zone, p.y(), p.x())).collect();
+        let mut cells: HashMap<Cell, Vec<PlatformIndex>> = HashMap::new();
+        for (index, point) in points.iter().enumerate() {
+            cells.entry(cell(point)).or_default().push(index);
+        }
+        let min = (
+            cells.keys().map(|c| c.0).min().unwrap(),
+            cells.keys().map(|c| c.1).min().unwrap(),
+        );
+        let max = (
+            cells.keys().map(|c| c.0).max().unwrap(),
+            cells.keys().map(|c| c.1).max().unwrap(),
+        );
+        Self {
+            zone,
+            points,
+            cells,
+            min,
+            max,
+        }
+    }
+
+    pub fn project(&self, point: &Point<f64>) -> Point<f64> {
+        project(self.zone, point.y(), point.x())
+    }
+
+    pub fn within(&self, point: &Point<f64>, radius: f64) -> Vec<(PlatformIndex, f64)> {
+        let center = self.project(point);
+        let (cx, cy) = cell(&center);
+        let reach = (radius / CELL).ceil() as i64;
+        let mut found = vec![];
+        for x in (cx - reach).max(self.min.0)..=(cx + reach).min(self.max.0) {
+            for y in (cy - reach).max(self.min.1)..=(cy + reach).min(self.max.1) {
+                for index in self.cells.get(&(x, y)).into_iter().flatten() {
+                    let distance = distance(&center, &self.points[*index]);
+                    if distance < radius {
+                        found.push((*index, distance));
+                    }
+                }
+            }
+        }
+        found.sort_by(|a, b| a.1.total_cmp(&b.1));
+        found
+    }
+
+    pub fn nearest(&self, point: &Point<f64>, k: usize) -> Vec<(PlatformIndex, f64)> {
+        if k == 0 || self.points.is_empty() {
+            return vec![];
+        }
+        let center = self.project(point);
+        let (cx, cy) = cell(&center);
+        let last = [
+            cx - self.min.0,
+            self.max.0 - cx,
+            cy - self.min.1,
+            self.max.1 - cy,
+        ]
+        .into_iter()
+        .max()
+        .unwrap();
+        let first = [
+            self.min.0 - cx,
+            cx - self.max.0,
+            self.min.1 - cy,
+            cy - self.max.1,
+            0,
+        ]
+        .into_iter()
+        .max()
+        .unwrap();
+        let mut found: Vec<(PlatformIndex, f64)> = vec![];
+        for ring in first..=last {
+            for (x, y) in ring_cells(cx, cy, ring) {
+                for index in self.cells.get(&(x, y)).into_iter().flatten() {
+                    found.push((*index, distance(&center, &self.points[*index])));
+                }
+            }
+            found.sort_by(|a, b| a.1.total_cmp(&b.1));
+            // Everything closer than the ring has already been seen
+            if found.len() >= k && found[k - 1].1 <= ring as f64 * CELL {
+                break;
+            }
+        }
+        found.truncate(k);
+        found
+    }
+}
+
+fn project(zone: u8, lat: f64, lon: f64) -> Point<f64> {
+    let (x, y, _) = utm::to_utm_wgs84(lat, lon, zone);
+    Point::new(x, y)
+}
+
+fn cell(point: &Point<f64>) -> Cell {
+    (
+        (point.x() / CELL).floor() as i64,
+        (point.y() / CELL).floor() as i64,
+    )
+}
+
+fn ring_cells(cx: i64, cy: i64, ring: i64) -> Vec<Cell> {
+    if ring == 0 {
+        return vec![(cx, cy)];
+    }
+    let mut cells = vec![];
+    for x in (cx - ring)..=(cx + ring) {
+        cells.push((x, cy - ring));
+        cells.push((x, cy + ring));
+    }
+    for y in (cy - ring + 1)..(cy + ring) {
+        cells.push((cx - ring, y));
+        cells.push((cx + ring, y));
+    }
+    cells
+}
+
+pub(crate) fn distance(lhs: &Point<f64>, rhs: &Point<f64>) -> f64 {
+    let dx = lhs.x() - rhs.x();
+    let dy = lhs.y() - rhs.y();
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod grid {
+    use super::*;
+
+    use crate::map::Point as GeoPoint;
+
+    // About 111 m between neighbouring platforms along a meridian
+    fn platforms() -> Vec<Platform> {
+        (0..20)
+            .map(|i| Platform::new(GeoPoint::new(55.0 + 0.001 * i as f64, 37.0), vec![]))
+            .collect()
+    }
+
+    fn at(lat: f64, lon: f64) -> Point<f64> {
+        Point::new(lon, lat)
+    }
+
+    #[test]
+    fn within_radius() {
+        let grid = Grid::new(&platforms());
+        let found: Vec<_> = grid
+            .within(&at(55.005, 37.0), 250.0)
+            .iter()
+            .map(|f| f.0)
+            .collect();
+        assert_eq!(5, found[0]);
+        let mut found = found;
+        found.sort();
+        assert_eq!(vec![3, 4, 5, 6, 7], found);
+    }
+
+    #[test]
+    fn nothing_within_radius() {
+        let grid = Grid::new(&platforms());
+        assert!(grid.within(&at(56.0, 37.0), 1000.0).is_empty());
+    }
+
+    #[test]
+    fn k_nearest() {
+        let grid = Grid::new(&platforms());
+        let found: Vec<_> = grid
+            .nearest(&at(55.0101, 37.0), 3)
+            .iter()
+            .map(|f| f.0)
+            .collect();
+        assert_eq!(vec![10, 11, 9], found);
+    }
+
+    #[test]
+    fn nearest_far_away() {
+        let grid = Grid::new(&platforms());
+        let found = grid.nearest(&at(55.1, 37.0), 1);
+        assert_eq!(19, found[0].0);
+    }
+
+    #[test]
+    fn fewer_than_k() {
+        let grid = Grid::new(&platforms()[..2]);
+        assert_eq!(2, grid.nearest(&at(55.0, 37.0), 5).len());
+    }
+
+    #[test]
+    fn empty() {
+        let grid = Grid::new(&[]);
+        assert!(grid.nearest(&at(55.0, 37.0), 1).is_empty());
+        assert!(grid.within(&at(55.0, 37.0), 1000.0).is_empty());
+    }
+}
@@ -0,0 +1,361 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
+
+use geo_types::{Coord, Point};
+use osmpbf::{Element, ElementReader};
+
+use crate::map::{PublicTransport, Time};
+use crate::platforms::WalkingProfile;
+use crate::spatial::{self, Grid};
+
+pub type NodeIndex = usize;
+
+// A point farther than this from any street is off the network
+const SNAP: f64 = 500.0;
+// Longest street path searched relative to the straight line
+const MAX_DETOUR: f64 = 3.0;
+
+const CLOSED: [&str; 8] = [
+    "motorway",
+    "motorway_link",
+    "trunk",
+    "trunk_link",
+    "construction",
+    "proposed",
+    "bus_guideway",
+    "raceway",
+];
+
+#[derive(Debug)]
+pub enum Error {
+    Pbf(osmpbf::Error),
+    Empty,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Pbf(e) => write!(f, "{}", e),
+            Error::Empty => write!(f, "no walkable streets found"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<osmpbf::Error> for Error {
+    fn from(e: osmpbf::Error) -> Self {
+        Error::Pbf(e)
+    }
+}
+
+#[derive(PartialEq)]
+struct Visit(f64, NodeIndex);
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0)
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+type Tree = HashMap<NodeIndex, (f64, NodeIndex)>;
+
+#[derive(Debug, Default)]
+pub struct Streets {
+    nodes: Vec<Point<f64>>,
+    edges: Vec<Vec<(NodeIndex, f64)>>,
+    grid: Grid,
+}
+
+impl Streets {
+    pub fn new(nodes: Vec<Point<f64>>, ways: &[Vec<NodeIndex>]) -> Self {
+        let grid = Grid::from_points(&nodes);
+        let projected: Vec<_> = nodes.iter().map(|n| grid.project(n)).collect();
+        let mut edges = vec![vec![]; nodes.len()];
+        for way in ways {
+            for pair in way.windows(2) {
+                let length = spatial::distance(&projected[pair[0]], &projected[pair[1]]);
+                edges[pair[0]].push((pair[1], length));
+                edges[pair[1]].push((pair[0], length));
+            }
+        }
+        Self { nodes, edges, grid }
+    }
+
+    pub fn covers(&self, point: &Point<f64>) -> bool {
+        self.snap(point).is_some()
+    }
+
+    pub fn distances(
+        &self,
+        point: &Point<f64>,
+        targets: &[Point<f64>],
+        limit: f64,
+    ) -> Vec<Option<f64>> {
+        let Some((source, start)) = self.snap(point) else {
+            return vec![None; targets.len()];
+        };
+        let snapped: Vec<_> = targets.iter().map(|t| self.snap(t)).collect();
+        let nodes: Vec<_> = snapped.iter().flatten().map(|s| s.0).collect();
+        let tree = self.explore(source, limit, &nodes);
+        snapped
+            .into_iter()
+            .map(|s| {
+                let (node, finish) = s?;
+                let distance = start + self.distance(&tree, source, node)? + finish;
+                (distance <= limit).then_some(distance)
+            })
+            .collect()
+    }
+
+    pub fn route(&self, from: &Point<f64>, to: &Point<f64>) -> Option<Vec<Coord<f64>>> {
+        let (source, _) = self.snap(from)?;
+        let (target, _) = self.snap(to)?;
+        let straight = spatial::distance(&self.grid.project(from), &self.grid.project(to));
+        let limit = reach(straight);
+        let tree = self.explore(source, limit, &[target]);
+        self.distance(&tree, source, target)?;
+        let mut nodes = vec![target];
+        while let Some((_, previous)) = tree.get(nodes.last().unwrap()) {
+            nodes.push(*previous);
+        }
+        let mut points = vec![from.0];
+        points.extend(nodes.iter().rev().map(|n| self.nodes[*n].0));
+        points.push(to.0);
+        Some(points)
+    }
+
+    pub fn update_passages(&self, map: &mut PublicTransport, profile: &WalkingProfile) {
+        let points: Vec<_> = map
+            .platforms
+            .iter()
+            .map(|p| Point::new(p.point.lon, p.point.lat))
+            .collect();
+        for (from, passages) in map.passages.iter_mut().enumerate() {
+            let targets: Vec<_> = passages.iter().map(|p| points[p.to]).collect();
+            let center = self.grid.project(&points[from]);
+            let limit = targets
+                .iter()
+                .map(|t| {
+                    spatial::distance(&center, &self.grid.project(t)) * MAX_DETOUR + 2.0 * SNAP
+                })
+                .fold(0.0, f64::max);
+            let distances = self.distances(&points[from], &targets, limit);
+            for (passage, distance) in passages.iter_mut().zip(distances) {
+                if let Some(distance) = distance {
+                    passage.time = duration(distance, profile);
+                }
+            }
+        }
+    }
+
+    fn snap(&self, point: &Point<f64>) -> Option<(NodeIndex, f64)> {
+        self.grid
+            .nearest(point, 1)
+            .into_iter()
+            .find(|(_, distance)| *distance <= SNAP)
+    }
+
+    fn distance(&self, tree: &Tree, source: NodeIndex, target: NodeIndex) -> Option<f64> {
+        match target == source {
+            true => Some(0.0),
+            false => tree.get(&target).map(|t| t.0),
+        }
+    }
+
+    // Dijkstra until all targets are settled or the limit is reached
+    fn explore(&self, source: NodeIndex, limit: f64, targets: &[NodeIndex]) -> Tree {
+        let mut left: HashSet<_> = targets.iter().copied().filter(|t| *t != source).collect();
+        let mut tree = Tree::new();
+        let mut settled = HashSet::new();
+        let mut heap = BinaryHeap::from([Visit(0.0, source)]);
+        while let Some(Visit(distance, node)) = heap.pop() {
+            if left.is_empty() || distance > limit {
+                break;
+            }
+            if !settled.insert(node) {
+                continue;
+            }
+            left.remove(&node);
+            for (next, length) in &self.edges[node] {
+                let reached = distance + length;
+                let known = tree.get(next).map_or(f64::MAX, |t| t.0);
+                if *next != source && reached < known {
+                    tree.insert(*next, (reached, node));
+                    heap.push(Visit(reached, *next));
+                }
+            }
+        }
+        tree.retain(|node, _| settled.contains(node));
+        tree
+    }
+}
+
+fn reach(straight: f64) -> f64 {
+    straight * MAX_DETOUR + 2.0 * SNAP
+}
+
+pub fn duration(distance: f64, profile: &WalkingProfile) -> Time {
+    (distance / profile.speed).round() as Time
+}
+
+fn is_walkable<'a>(tags: impl Iterator<Item = (&'a str, &'a str)>) -> bool {
+    let mut highway = None;
+    let mut foot = None;
+    for (key, value) in tags {
+        match key {
+            "highway" => highway = Some(value),
+            "foot" => foot = Some(value),
+            _ => (),
+        }
+    }
+    match (highway, foot) {
+        (None, _) | (_, Some("no" | "private")) => false,
+        (Some(_), Some("yes" | "designated")) => true,
+        (Some(highway), _) => !CLOSED.contains(&highway),
+    }
+}
+
+pub fn read_streets(filename: &str) -> Result<Streets, Error> {
+    let mut ways: Vec<Vec<i64>> = vec![];
+    ElementReader::from_path(filename)?.for_each(|element| {
+        if let Element::Way(way) = element {
+            if is_walkable(way.tags()) {
+                ways.push(way.refs().collect());
+            }
+        }
+    })?;
+    let needed: HashSet<i64> = ways.iter().flatten().copied().collect();
+    let mut coordinates: HashMap<i64, Point<f64>> = HashMap::new();
+    ElementReader::from_path(filename)?.for_each(|element| {
+        let (id, lat, lon) = match element {
+            Element::Node(node) => (node.id(), node.lat(), node.lon()),
+            Element::DenseNode(node) => (node.id(), node.lat(), node.lon()),
+            _ => return,
+        };
+        if needed.contains(&id) {
+            coordinates.insert(id, Point::new(lon, lat));
+        }
+    })?;
+    let mut index: HashMap<i64, NodeIndex> = HashMap::new();
+    let mut nodes = vec![];
+    let mut indexed = vec![];
+    for way in ways {
+        // A node cut off by the extract splits its way
+        for piece in way.split(|id| !coordinates.contains_key(id)) {
+            let piece: Vec<_> = piece
+                .iter()
+                .map(|id| {
+                    *index.entry(*id).or_insert_with(|| {
+                        nodes.push(coordinates[id]);
+                        nodes.len() - 1
+                    })
+                })
+                .collect();
+            indexed.push(piece);
+        }
+    }
+    if nodes.is_empty() {
+        return Err(Error::Empty);
+    }
+    Ok(Streets::new(nodes, &indexed))
+}
+
+#[cfg(test)]
+mod graph {
+    use super::*;
+
+    use crate::map::{Passage, Platform, Point as GeoPoint};
+
+    fn at(lat: f64, lon: f64) -> Point<f64> {
+        Point::new(lon, lat)
+    }
+
+    // Two banks 128 m apart joined by a bridge 555 m upstream
+    fn streets() -> Streets {
+        Streets::new(
+            vec![
+                at(55.0, 37.0),
+                at(55.0, 37.002),
+                at(55.005, 37.0),
+                at(55.005, 37.002),
+            ],
+            &[vec![0, 2], vec![2, 3], vec![3, 1]],
+        )
+    }
+
+    #[test]
+    fn around_river() {
+        let distances = streets().distances(&at(55.0, 37.0), &[at(55.0, 37.002)], 2000.0);
+        let distance = distances[0].unwrap();
+        assert!(distance > 1230.0 && distance < 1250.0);
+    }
+
+    #[test]
+    fn beyond_limit() {
+        let distances = streets().distances(&at(55.0, 37.0), &[at(55.0, 37.002)], 1000.0);
+        assert_eq!(vec![None], distances);
+    }
+
+    #[test]
+    fn off_network() {
+        let streets = streets();
+        assert!(!streets.covers(&at(55.1, 37.0)));
+        let distances = streets.distances(&at(55.1, 37.0), &[at(55.0, 37.002)], 2000.0);
+        assert_eq!(vec![None], distances);
+        assert!(streets.route(&at(55.0, 37.0), &at(55.1, 37.0)).is_none());
+    }
+
+    #[test]
+    fn follow_streets() {
+        let route = streets()
+            .route(&at(55.0, 37.0001), &at(55.0, 37.0019))
+            .unwrap();
+        let expected = vec![
+            at(55.0, 37.0001).0,
+            at(55.0, 37.0).0,
+            at(55.005, 37.0).0,
+            at(55.005, 37.002).0,
+            at(55.0, 37.002).0,
+            at(55.0, 37.0019).0,
+        ];
+        assert_eq!(expected, route);
+    }
+
+    #[test]
+    fn passages() {
+        let mut map = PublicTransport::new(
+            vec![
+                Platform::new(GeoPoint::new(55.0, 37.0), vec![]),
+                Platform::new(GeoPoint::new(55.0, 37.002), vec![]),
+            ],
+            vec![],
+            vec![vec![Passage::new(1, 100)], vec![]],
+        );
+        streets().update_passages(&mut map, &WalkingProfile::default());
+        let time = map.passages[0][0].time;
+        assert!(time > 880 && time < 900);
+    }
+
+    #[test]
+    fn walkable() {
+        assert!(is_walkable([("highway", "footway")].into_iter()));
+        assert!(is_walkable(
+            [("highway", "trunk"), ("foot", "yes")].into_iter()
+        ));
+        assert!(!is_walkable([("highway", "motorway")].into_iter()));
+        assert!(!is_walkable(
+            [("highway", "service"), ("foot", "no")].into_iter()
+        ));
+        assert!(!is_walkable([("railway", "rail")].into_iter()));
+    }
+}
@@ -0,0 +1,313 @@
+use std::fmt;
+
+use crate::calendar::ServiceIndex;
+use crate::map::{OrdinalNumber, PlatformIndex, PublicTransport, Route, RouteIndex, Trip};
+
+pub type TripNumber = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    UnknownPlatform(RouteIndex, PlatformIndex),
+    UnknownRoute(PlatformIndex, RouteIndex),
+    UnknownService(RouteIndex, TripNumber, ServiceIndex),
+    UnknownPassage(PlatformIndex, PlatformIndex),
+    ShortRoute(RouteIndex),
+    TripLength(RouteIndex, TripNumber, usize, usize),
+    DecreasingTime(RouteIndex, TripNumber, OrdinalNumber),
+    UnsortedTrips(RouteIndex, TripNumber, OrdinalNumber),
+    BadFrequency(RouteIndex, TripNumber),
+    MissingRoute(PlatformIndex, RouteIndex),
+    ExtraRoute(PlatformIndex, RouteIndex),
+    Passages(usize, usize),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::UnknownPlatform(route, platform) => {
+                write!(f, "route {} stops at unknown platform {}", route, platform)
+            }
+            Violation::UnknownRoute(platform, route) => {
+                write!(f, "platform {} refers to unknown route {}", platform, route)
+            }
+            Violation::UnknownService(route, trip, service) => write!(
+                f,
+                "trip {} of route {} refers to unknown service {}",
+                trip, route, service
+            ),
+            Violation::UnknownPassage(from, to) => {
+                write!(
+                    f,
+                    "passage from platform {} to unknown platform {}",
+                    from, to
+                )
+            }
+            Violation::ShortRoute(route) => {
+                write!(f, "route {} has less than two platforms", route)
+            }
+            Violation::TripLength(route, trip, expected, actual) => write!(
+                f,
+                "trip {} of route {} has {} stops instead of {}",
+                trip, route, actual, expected
+            ),
+            Violation::DecreasingTime(route, trip, ordinal) => write!(
+                f,
+                "trip {} of route {} goes back in time at stop {}",
+                trip, route, ordinal
+            ),
+            Violation::UnsortedTrips(route, trip, ordinal) => write!(
+                f,
+                "trip {} of route {} departs before previous trip at stop {}",
+                trip, route, ordinal
+            ),
+            Violation::BadFrequency(route, trip) => write!(
+                f,
+                "trip {} of route {} has a frequency without runs",
+                trip, route
+            ),
+            Violation::MissingRoute(platform, route) => write!(
+                f,
+                "route {} stops at platform {} but the platform does not list it",
+                route, platform
+            ),
+            Violation::ExtraRoute(platform, route) => write!(
+                f,
+                "platform {} lists route {} which does not stop there",
+                platform, route
+            ),
+            Violation::Passages(expected, actual) => write!(
+                f,
+                "passages are given for {} platforms instead of {}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl PublicTransport {
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = vec![];
+        for (index, route) in self.routes.iter().enumerate() {
+            self.validate_route(index, route, &mut violations);
+        }
+        self.validate_platforms(&mut violations);
+        self.validate_passages(&mut violations);
+        violations
+    }
+
+    fn validate_route(&self, index: RouteIndex, route: &Route, violations: &mut Vec<Violation>) {
+        let platforms = route.platforms();
+        if platforms.len() < 2 {
+            violations.push(Violation::ShortRoute(index));
+        }
+        for platform in platforms {
+            match self.platforms.get(*platform) {
+                None => violations.push(Violation::UnknownPlatform(index, *platform)),
+                Some(p) if !p.routes.contains(&index) => {
+                    violations.push(Violation::MissingRoute(*platform, index))
+                }
+                _ => (),
+            }
+        }
+        let length = match route.circle {
+            true => platforms.len() + 1,
+            false => platforms.len(),
+        };
+        let mut previous: Option<&[_]> = None;
+        for (number, trip) in route.trips().iter().enumerate() {
+            if !self.validate_trip(index, number, trip, length, violations) {
+                previous = None;
+                continue;
+            }
+            let stops = trip.stops();
+            if let Some(previous) = previous {
+                let overtaken = previous.iter().zip(stops).position(|(a, b)| b < a);
+                if let Some(ordinal) = overtaken {
+                    violations.push(Violation::UnsortedTrips(index, number, ordinal));
+                }
+            }
+            previous = Some(stops);
+        }
+        // Frequent trips are numbered after the others as they are stored apart
+        let scheduled = route.trips().len();
+        for (number, trip) in route.templates().iter().enumerate() {
+            let number = scheduled + number;
+            self.validate_trip(index, number, trip, length, violations);
+            let frequencies = trip.frequencies();
+            if frequencies
+                .iter()
+                .any(|f| f.headway <= 0 || f.end <= f.start)
+            {
+                violations.push(Violation::BadFrequency(index, number));
+            }
+        }
+    }
+
+    // Tells whether the trip has the right length to be checked further
+    fn validate_trip(
+        &self,
+        index: RouteIndex,
+        number: TripNumber,
+        trip: &Trip,
+        length: usize,
+        violations: &mut Vec<Violation>,
+    ) -> bool {
+        let stops = trip.stops();
+        if stops.len() != length {
+            violations.push(Violation::TripLength(index, number, length, stops.len()));
+            return false;
+        }
+        if let Some(ordinal) = stops.windows(2).position(|w| w[1] < w[0]) {
+            violations.push(Violation::DecreasingTime(index, number, ordinal + 1));
+        }
+        if let Some(service) = trip.service() {
+            if service >= self.services.len() {
+                violations.push(Violation::UnknownService(index, number, service));
+            }
+        }
+        true
+    }
+
+    fn validate_platforms(&self, violations: &mut Vec<Violation>) {
+        for (index, platform) in self.platforms.iter().enumerate() {
+            for route in &platform.routes {
+                match self.routes.get(*route) {
+                    None => violations.push(Violation::UnknownRoute(index, *route)),
+                    Some(r) if !r.platforms().contains(&index) => {
+                        violations.push(Violation::ExtraRoute(index, *route))
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    fn validate_passages(&self, violations: &mut Vec<Violation>) {
+        if self.passages.len() != self.platforms.len() {
+            violations.push(Violation::Passages(
+                self.platforms.len(),
+                self.passages.len(),
+            ));
+        }
+        for (from, passages) in self.passages.iter().enumerate() {
+            for passage in passages {
+                if passage.to >= self.platforms.len() {
+                    violations.push(Violation::UnknownPassage(from, passage.to));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod violations {
+    use super::*;
+
+    use crate::map::{Frequency, Passage, Platform, Point};
+
+    fn platforms(routes: Vec<Vec<RouteIndex>>) -> Vec<Platform> {
+        routes
+            .into_iter()
+            .map(|routes| Platform::new(Point::new(0.0, 0.0), routes))
+            .collect()
+    }
+
+    fn map(trips: Vec<Trip>) -> PublicTransport {
+        PublicTransport::new(
+            platforms(vec![vec![0], vec![0], vec![0]]),
+            vec![Route::new(false, vec![0, 1, 2], trips)],
+            vec![vec![]; 3],
+        )
+    }
+
+    #[test]
+    fn valid() {
+        let map = map(vec![
+            Trip::new(1, vec![10, 20, 30]),
+            Trip::new(2, vec![20, 30, 40]),
+        ]);
+        assert!(map.validate().is_empty());
+    }
+
+    #[test]
+    fn unknown_platform() {
+        let map = PublicTransport::new(
+            platforms(vec![vec![0]]),
+            vec![Route::new(false, vec![0, 5], vec![])],
+            vec![vec![]],
+        );
+        assert_eq!(vec![Violation::UnknownPlatform(0, 5)], map.validate());
+    }
+
+    #[test]
+    fn trip_length() {
+        let map = map(vec![Trip::new(1, vec![10, 20])]);
+        assert_eq!(vec![Violation::TripLength(0, 0, 3, 2)], map.validate());
+    }
+
+    #[test]
+    fn circle_trip_length() {
+        let map = PublicTransport::new(
+            platforms(vec![vec![0], vec![0]]),
+            vec![Route::new(
+                true,
+                vec![0, 1],
+                vec![Trip::new(1, vec![10, 20])],
+            )],
+            vec![vec![]; 2],
+        );
+        assert_eq!(vec![Violation::TripLength(0, 0, 3, 2)], map.validate());
+    }
+
+    #[test]
+    fn decreasing_time() {
+        let map = map(vec![Trip::new(1, vec![10, 30, 20])]);
+        assert_eq!(vec![Violation::DecreasingTime(0, 0, 2)], map.validate());
+    }
+
+    #[test]
+    fn unsorted_trips() {
+        let map = map(vec![
+            Trip::new(1, vec![10, 20, 50]),
+            Trip::new(2, vec![15, 25, 40]),
+        ]);
+        assert_eq!(vec![Violation::UnsortedTrips(0, 1, 2)], map.validate());
+    }
+
+    #[test]
+    fn bad_frequency() {
+        let map = map(vec![
+            Trip::new(1, vec![10, 20, 30]),
+            Trip::new(2, vec![0, 10, 20]).with_frequencies(vec![Frequency::new(100, 100, 60)]),
+            Trip::new(3, vec![0, 10]).with_frequencies(vec![Frequency::new(0, 100, 60)]),
+        ]);
+        let expected = vec![
+            Violation::BadFrequency(0, 1),
+            Violation::TripLength(0, 2, 3, 2),
+        ];
+        assert_eq!(expected, map.validate());
+    }
+
+    #[test]
+    fn platform_routes() {
+        let map = PublicTransport::new(
+            platforms(vec![vec![0], vec![], vec![0, 1], vec![0]]),
+            vec![Route::new(false, vec![0, 1, 2], vec![])],
+            vec![vec![]; 4],
+        );
+        let expected = vec![
+            Violation::MissingRoute(1, 0),
+            Violation::UnknownRoute(2, 1),
+            Violation::ExtraRoute(3, 0),
+        ];
+        assert_eq!(expected, map.validate());
+    }
+
+    #[test]
+    fn passages() {
+        let mut map = map(vec![]);
+        map.passages = vec![vec![Passage::new(7, 60)]];
+        let expected = vec![Violation::Passages(3, 1), Violation::UnknownPassage(0, 7)];
+        assert_eq!(expected, map.validate());
+    }
+}
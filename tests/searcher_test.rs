@@ -1,9 +1,16 @@
+use chrono::NaiveDate;
 use geo_types::coord;
 
 use tranet::{
-    map::{Passage, Platform, Point, PublicTransport, Route, Trip},
+    calendar::Service,
+    changes::ChangeTimes,
+    map::{Frequency, Passage, Platform, Point, PublicTransport, Route, Time, Trip},
+    mcraptor::{Criteria, McSearcher},
+    options::Options,
     path::{Part, Path},
     platforms::{Platforms, Walking},
+    realtime::Delay,
+    reverse::ReverseSearcher,
     searcher::Searcher,
 };
 
@@ -25,7 +32,7 @@ fn one_route() {
         vec![vec![]; 5],
     );
     let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(4, 10)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![Part::new(
             vec![
@@ -36,9 +43,14 @@ fn one_route() {
                 coord! {x: 5., y: 0.},
             ],
             Some(0),
-        )],
+        )
+        .with_platforms(Some(0), Some(4))
+        .with_times(10, 50)
+        .with_waiting(4)],
+        1,
         60,
-    )];
+    )
+    .with_walking(15)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -60,7 +72,7 @@ fn loop_route() {
         vec![vec![]; 5],
     );
     let platforms = Platforms::from(Walking::from([(1, 5)]), Walking::from([(4, 10)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![Part::new(
             vec![
@@ -71,9 +83,14 @@ fn loop_route() {
                 coord! {x: 5., y: 0.},
             ],
             Some(0),
-        )],
+        )
+        .with_platforms(Some(1), Some(4))
+        .with_times(20, 60)
+        .with_waiting(14)],
+        1,
         70,
-    )];
+    )
+    .with_walking(15)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -106,7 +123,7 @@ fn two_cross_route() {
         vec![vec![]; 9],
     );
     let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(4, 10)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![Part::new(
             vec![
@@ -117,9 +134,14 @@ fn two_cross_route() {
                 coord! {x: 5., y: 0.},
             ],
             Some(0),
-        )],
+        )
+        .with_platforms(Some(0), Some(4))
+        .with_times(10, 50)
+        .with_waiting(4)],
+        1,
         60,
-    )];
+    )
+    .with_walking(15)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -151,7 +173,7 @@ fn two_parallel_route() {
         vec![vec![]; 8],
     );
     let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(4, 10)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![Part::new(
             vec![
@@ -162,9 +184,14 @@ fn two_parallel_route() {
                 coord! {x: 5., y: 0.},
             ],
             Some(0),
-        )],
+        )
+        .with_platforms(Some(0), Some(4))
+        .with_times(10, 50)
+        .with_waiting(4)],
+        1,
         60,
-    )];
+    )
+    .with_walking(15)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -203,7 +230,7 @@ fn direct_faster() {
         vec![vec![]; 10],
     );
     let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(4, 10)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![Part::new(
             vec![
@@ -214,9 +241,14 @@ fn direct_faster() {
                 coord! {x: 5., y: 0.},
             ],
             Some(0),
-        )],
+        )
+        .with_platforms(Some(0), Some(4))
+        .with_times(10, 50)
+        .with_waiting(4)],
+        1,
         60,
-    )];
+    )
+    .with_walking(15)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -255,7 +287,7 @@ fn direct_slower() {
         vec![vec![]; 10],
     );
     let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(4, 10)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![
         Path::new(
             vec![Part::new(
@@ -267,9 +299,14 @@ fn direct_slower() {
                     coord! {x: 5., y: 0.},
                 ],
                 Some(0),
-            )],
+            )
+            .with_platforms(Some(0), Some(4))
+            .with_times(10, 50)
+            .with_waiting(4)],
+            1,
             60,
-        ),
+        )
+        .with_walking(15),
         Path::new(
             vec![
                 Part::new(
@@ -280,7 +317,10 @@ fn direct_slower() {
                         coord! {x: 12., y: 0.},
                     ],
                     Some(1),
-                ),
+                )
+                .with_platforms(Some(0), Some(7))
+                .with_times(7, 25)
+                .with_waiting(1),
                 Part::new(
                     vec![
                         coord! {x: 12., y: 0.},
@@ -289,10 +329,15 @@ fn direct_slower() {
                         coord! {x: 5., y: 0.},
                     ],
                     Some(2),
-                ),
+                )
+                .with_platforms(Some(7), Some(4))
+                .with_times(30, 45)
+                .with_waiting(5),
             ],
+            1,
             55,
-        ),
+        )
+        .with_walking(15),
     ];
     assert_eq!(expected, searcher.run(1));
 }
@@ -336,7 +381,7 @@ fn do_transfer() {
         ],
     );
     let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(8, 10)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![
             Part::new(
@@ -346,15 +391,26 @@ fn do_transfer() {
                     coord! {x: 3., y: 0.},
                 ],
                 Some(0),
-            ),
-            Part::new(vec![coord! {x: 3., y: 0.}, coord! {x: 12., y: 0.}], None),
+            )
+            .with_platforms(Some(0), Some(2))
+            .with_times(10, 30)
+            .with_waiting(4),
+            Part::new(vec![coord! {x: 3., y: 0.}, coord! {x: 12., y: 0.}], None)
+                .with_platforms(Some(2), Some(7))
+                .with_times(30, 35)
+                .with_walking(5),
             Part::new(
                 vec![coord! {x: 12., y: 0.}, coord! {x: 13., y: 0.}],
                 Some(1),
-            ),
+            )
+            .with_platforms(Some(7), Some(8))
+            .with_times(40, 60)
+            .with_waiting(5),
         ],
+        1,
         70,
-    )];
+    )
+    .with_walking(20)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -380,7 +436,7 @@ fn circle() {
         vec![vec![]; 5],
     );
     let platforms = Platforms::from(Walking::from([(1, 5)]), Walking::from([(3, 10)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![Part::new(
             vec![
@@ -389,9 +445,14 @@ fn circle() {
                 coord! {x: 4., y: 0.},
             ],
             Some(0),
-        )],
+        )
+        .with_platforms(Some(1), Some(3))
+        .with_times(20, 40)
+        .with_waiting(14)],
+        1,
         50,
-    )];
+    )
+    .with_walking(15)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -417,7 +478,7 @@ fn circle_on_seam() {
         vec![vec![]; 5],
     );
     let platforms = Platforms::from(Walking::from([(3, 5)]), Walking::from([(1, 5)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![Part::new(
             vec![
@@ -427,9 +488,14 @@ fn circle_on_seam() {
                 coord! {x: 2., y: 0.},
             ],
             Some(0),
-        )],
+        )
+        .with_platforms(Some(3), Some(1))
+        .with_times(40, 70)
+        .with_waiting(34)],
+        1,
         75,
-    )];
+    )
+    .with_walking(10)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -455,10 +521,13 @@ fn circle_on_seam_with_change_vehicle() {
         vec![vec![]; 5],
     );
     let platforms = Platforms::from(Walking::from([(3, 5)]), Walking::from([(1, 5)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![
-            Part::new(vec![coord! {x: 4., y: 0.}, coord! {x: 5., y: 0.}], Some(0)),
+            Part::new(vec![coord! {x: 4., y: 0.}, coord! {x: 5., y: 0.}], Some(0))
+                .with_platforms(Some(3), Some(4))
+                .with_times(40, 50)
+                .with_waiting(34),
             Part::new(
                 vec![
                     coord! {x: 5., y: 0.},
@@ -466,10 +535,14 @@ fn circle_on_seam_with_change_vehicle() {
                     coord! {x: 2., y: 0.},
                 ],
                 Some(0),
-            ),
+            )
+            .with_platforms(Some(4), Some(1))
+            .with_times(50, 80),
         ],
+        1,
         85,
-    )];
+    )
+    .with_walking(10)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -506,7 +579,7 @@ fn bidirectional_circle() {
         vec![vec![]; 5],
     );
     let platforms = Platforms::from(Walking::from([(3, 5)]), Walking::from([(1, 5)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![Part::new(
             vec![
@@ -515,9 +588,14 @@ fn bidirectional_circle() {
                 coord! {x: 2., y: 0.},
             ],
             Some(1),
-        )],
+        )
+        .with_platforms(Some(3), Some(1))
+        .with_times(25, 45)
+        .with_waiting(19)],
+        1,
         50,
-    )];
+    )
+    .with_walking(10)];
     assert_eq!(expected, searcher.run(1));
 }
 
@@ -554,7 +632,7 @@ fn bidirectional_circle_on_seam() {
         vec![vec![]; 5],
     );
     let platforms = Platforms::from(Walking::from([(1, 5)]), Walking::from([(3, 5)]));
-    let mut searcher = Searcher::new(&map, platforms);
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
     let expected: Vec<Path> = vec![Path::new(
         vec![Part::new(
             vec![
@@ -564,8 +642,578 @@ fn bidirectional_circle_on_seam() {
                 coord! {x: 4., y: 0.},
             ],
             Some(1),
-        )],
+        )
+        .with_platforms(Some(1), Some(3))
+        .with_times(45, 75)
+        .with_waiting(14)],
+        26,
         80,
-    )];
+    )
+    .with_walking(10)];
     assert_eq!(expected, searcher.run(26));
 }
+
+#[test]
+fn profile_each_departure() {
+    let map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0]),
+            Platform::new(Point::new(0., 3.), vec![0]),
+        ],
+        vec![Route::new(
+            false,
+            vec![0, 1, 2],
+            vec![
+                Trip::new(1, vec![10, 20, 30]),
+                Trip::new(2, vec![40, 50, 60]),
+                Trip::new(3, vec![70, 80, 90]),
+            ],
+        )],
+        vec![vec![]; 3],
+    );
+    let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(2, 0)]));
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
+    let part = |departure| {
+        Part::new(
+            vec![
+                coord! {x: 1., y: 0.},
+                coord! {x: 2., y: 0.},
+                coord! {x: 3., y: 0.},
+            ],
+            Some(0),
+        )
+        .with_platforms(Some(0), Some(2))
+        .with_times(departure, departure + 20)
+    };
+    let expected: Vec<Path> = vec![
+        Path::new(vec![part(70)], 65, 90).with_walking(5),
+        Path::new(vec![part(40)], 35, 60).with_walking(5),
+        Path::new(vec![part(10)], 5, 30).with_walking(5),
+    ];
+    assert_eq!(expected, searcher.profile(0..100));
+}
+
+#[test]
+fn profile_window() {
+    let map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0]),
+        ],
+        vec![Route::new(
+            false,
+            vec![0, 1],
+            vec![
+                Trip::new(1, vec![10, 20]),
+                Trip::new(2, vec![40, 50]),
+                Trip::new(3, vec![70, 80]),
+            ],
+        )],
+        vec![vec![]; 2],
+    );
+    let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(1, 0)]));
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
+    let departures: Vec<_> = searcher
+        .profile(30..65)
+        .iter()
+        .map(|p| p.departure)
+        .collect();
+    assert_eq!(vec![35], departures);
+}
+
+#[test]
+fn profile_fewer_transfers() {
+    let map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0, 1]),
+            Platform::new(Point::new(0., 2.), vec![1, 2]),
+            Platform::new(Point::new(0., 3.), vec![0, 2]),
+        ],
+        vec![
+            Route::new(false, vec![0, 2], vec![Trip::new(1, vec![10, 100])]),
+            Route::new(false, vec![0, 1], vec![Trip::new(2, vec![10, 20])]),
+            Route::new(false, vec![1, 2], vec![Trip::new(3, vec![30, 40])]),
+        ],
+        vec![vec![]; 3],
+    );
+    let platforms = Platforms::from(Walking::from([(0, 0)]), Walking::from([(2, 0)]));
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
+    let paths = searcher.profile(0..100);
+    let arrivals: Vec<_> = paths.iter().map(|p| (p.transfers(), p.arrival)).collect();
+    assert_eq!(vec![(0, 100), (1, 40)], arrivals);
+}
+
+#[test]
+fn arrive_by_one_route() {
+    let map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0]),
+            Platform::new(Point::new(0., 3.), vec![0]),
+        ],
+        vec![Route::new(
+            false,
+            vec![0, 1, 2],
+            vec![
+                Trip::new(1, vec![10, 20, 30]),
+                Trip::new(2, vec![40, 50, 60]),
+                Trip::new(3, vec![70, 80, 90]),
+            ],
+        )],
+        vec![vec![]; 3],
+    );
+    let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(2, 10)]));
+    let mut searcher = ReverseSearcher::new(&map, platforms);
+    let expected: Vec<Path> = vec![Path::new(
+        vec![Part::new(
+            vec![
+                coord! {x: 1., y: 0.},
+                coord! {x: 2., y: 0.},
+                coord! {x: 3., y: 0.},
+            ],
+            Some(0),
+        )
+        .with_platforms(Some(0), Some(2))
+        .with_times(40, 60)],
+        35,
+        70,
+    )
+    .with_walking(15)];
+    assert_eq!(expected, searcher.run(95));
+}
+
+#[test]
+fn arrive_by_fewer_transfers() {
+    let map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0, 1]),
+            Platform::new(Point::new(0., 2.), vec![1, 2]),
+            Platform::new(Point::new(0., 3.), vec![0, 2]),
+        ],
+        vec![
+            Route::new(false, vec![0, 2], vec![Trip::new(1, vec![10, 100])]),
+            Route::new(false, vec![0, 1], vec![Trip::new(2, vec![50, 60])]),
+            Route::new(false, vec![1, 2], vec![Trip::new(3, vec![70, 80])]),
+        ],
+        vec![vec![]; 3],
+    );
+    let platforms = Platforms::from(Walking::from([(0, 0)]), Walking::from([(2, 0)]));
+    let mut searcher = ReverseSearcher::new(&map, platforms);
+    let expected: Vec<Path> = vec![
+        Path::new(
+            vec![
+                Part::new(vec![coord! {x: 1., y: 0.}, coord! {x: 3., y: 0.}], Some(0))
+                    .with_platforms(Some(0), Some(2))
+                    .with_times(10, 100),
+            ],
+            10,
+            100,
+        ),
+        Path::new(
+            vec![
+                Part::new(vec![coord! {x: 1., y: 0.}, coord! {x: 2., y: 0.}], Some(1))
+                    .with_platforms(Some(0), Some(1))
+                    .with_times(50, 60),
+                Part::new(vec![coord! {x: 2., y: 0.}, coord! {x: 3., y: 0.}], Some(2))
+                    .with_platforms(Some(1), Some(2))
+                    .with_times(70, 80)
+                    .with_waiting(10),
+            ],
+            50,
+            80,
+        ),
+    ];
+    assert_eq!(expected, searcher.run(100));
+}
+
+#[test]
+fn arrive_by_circle_on_seam() {
+    let map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0]),
+            Platform::new(Point::new(0., 3.), vec![0]),
+            Platform::new(Point::new(0., 4.), vec![0]),
+            Platform::new(Point::new(0., 5.), vec![0]),
+        ],
+        vec![Route::new(
+            true,
+            vec![0, 1, 2, 3, 4],
+            vec![
+                Trip::new(1, vec![10, 20, 30, 40, 50, 60]),
+                Trip::new(2, vec![30, 40, 50, 60, 70, 80]),
+                Trip::new(3, vec![60, 70, 80, 90, 100, 110]),
+            ],
+        )],
+        vec![vec![]; 5],
+    );
+    let platforms = Platforms::from(Walking::from([(3, 5)]), Walking::from([(1, 5)]));
+    let mut searcher = ReverseSearcher::new(&map, platforms);
+    let expected: Vec<Path> = vec![Path::new(
+        vec![Part::new(
+            vec![
+                coord! {x: 4., y: 0.},
+                coord! {x: 5., y: 0.},
+                coord! {x: 1., y: 0.},
+                coord! {x: 2., y: 0.},
+            ],
+            Some(0),
+        )
+        .with_platforms(Some(3), Some(1))
+        .with_times(40, 70)],
+        35,
+        75,
+    )
+    .with_walking(10)];
+    assert_eq!(expected, searcher.run(75));
+}
+
+fn walk_or_ride() -> PublicTransport {
+    PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0, 1]),
+            Platform::new(Point::new(0., 2.), vec![1]),
+            Platform::new(Point::new(0., 3.), vec![0, 2]),
+            Platform::new(Point::new(0., 4.), vec![2]),
+        ],
+        vec![
+            Route::new(false, vec![0, 2], vec![Trip::new(1, vec![10, 100])]),
+            Route::new(false, vec![0, 1], vec![Trip::new(2, vec![10, 20])]),
+            Route::new(false, vec![3, 2], vec![Trip::new(3, vec![40, 50])]),
+        ],
+        vec![vec![], vec![Passage::new(3, 10)], vec![], vec![]],
+    )
+}
+
+fn arrivals(paths: Vec<Path>) -> Vec<Time> {
+    paths.iter().map(|p| p.arrival).collect()
+}
+
+#[test]
+fn without_limits() {
+    let map = walk_or_ride();
+    let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(2, 5)]));
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
+    assert_eq!(vec![105, 55], arrivals(searcher.run(0)));
+}
+
+#[test]
+fn max_transfers() {
+    let map = walk_or_ride();
+    let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(2, 5)]));
+    let options = Options::default().with_max_transfers(0);
+    let mut searcher = Searcher::new(&map, platforms, options);
+    assert_eq!(vec![105], arrivals(searcher.run(0)));
+}
+
+#[test]
+fn max_duration() {
+    let map = walk_or_ride();
+    let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(2, 5)]));
+    let options = Options::default().with_max_duration(60);
+    let mut searcher = Searcher::new(&map, platforms, options);
+    assert_eq!(vec![55], arrivals(searcher.run(0)));
+}
+
+#[test]
+fn max_walking() {
+    let map = walk_or_ride();
+    let platforms = Platforms::from(Walking::from([(0, 5)]), Walking::from([(2, 5)]));
+    let options = Options::default().with_max_walking(15);
+    let mut searcher = Searcher::new(&map, platforms, options);
+    assert_eq!(vec![105], arrivals(searcher.run(0)));
+}
+
+fn change_at_platform() -> PublicTransport {
+    PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0, 1]),
+            Platform::new(Point::new(0., 3.), vec![1]),
+            Platform::new(Point::new(0., 4.), vec![2]),
+            Platform::new(Point::new(0., 5.), vec![2]),
+        ],
+        vec![
+            Route::new(false, vec![0, 1], vec![Trip::new(1, vec![10, 20])]),
+            Route::new(
+                false,
+                vec![1, 2],
+                vec![Trip::new(2, vec![20, 30]), Trip::new(3, vec![40, 50])],
+            ),
+            Route::new(
+                false,
+                vec![3, 4],
+                vec![Trip::new(4, vec![25, 35]), Trip::new(5, vec![45, 55])],
+            ),
+        ],
+        vec![vec![], vec![Passage::new(3, 5)], vec![], vec![], vec![]],
+    )
+}
+
+#[test]
+fn without_change_time() {
+    let map = change_at_platform();
+    let platforms = Platforms::from(Walking::from([(0, 0)]), Walking::from([(2, 0)]));
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
+    assert_eq!(vec![30], arrivals(searcher.run(0)));
+}
+
+#[test]
+fn global_change_time() {
+    let map = change_at_platform().with_changes(ChangeTimes::new(1));
+    let platforms = Platforms::from(Walking::from([(0, 0)]), Walking::from([(2, 0)]));
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
+    assert_eq!(vec![50], arrivals(searcher.run(0)));
+}
+
+#[test]
+fn no_change_time_from_origin() {
+    let map = change_at_platform().with_changes(ChangeTimes::new(60));
+    let platforms = Platforms::from(Walking::from([(1, 0)]), Walking::from([(2, 0)]));
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
+    assert_eq!(vec![30], arrivals(searcher.run(20)));
+}
+
+#[test]
+fn platform_pair_change_time() {
+    let platforms = || Platforms::from(Walking::from([(0, 0)]), Walking::from([(4, 0)]));
+    let map = change_at_platform();
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![35], arrivals(searcher.run(0)));
+
+    let mut changes = ChangeTimes::default();
+    changes.set_pair(1, 3, 10);
+    let map = change_at_platform().with_changes(changes);
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![55], arrivals(searcher.run(0)));
+}
+
+fn night_bus(trips: Vec<Trip>) -> PublicTransport {
+    let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let end = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+    PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0]),
+        ],
+        vec![Route::new(false, vec![0, 1], trips)],
+        vec![vec![], vec![]],
+    )
+    .with_services(vec![Service::new([true; 7], start, end)])
+}
+
+#[test]
+fn next_day_trip() {
+    let map = night_bus(vec![Trip::new(1, vec![600, 900]).with_service(0)]);
+    let platforms = Platforms::from(Walking::from([(0, 0)]), Walking::from([(1, 0)]));
+    let date = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+    let mut searcher = Searcher::new(&map, platforms, Options::default()).with_date(date);
+    assert_eq!(vec![87300], arrivals(searcher.run(85800)));
+}
+
+#[test]
+fn previous_day_trip_past_midnight() {
+    let map = night_bus(vec![Trip::new(1, vec![86700, 87000]).with_service(0)]);
+    let platforms = Platforms::from(Walking::from([(0, 0)]), Walking::from([(1, 0)]));
+    let date = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+    let mut searcher = Searcher::new(&map, platforms, Options::default()).with_date(date);
+    assert_eq!(vec![600], arrivals(searcher.run(0)));
+}
+
+#[test]
+fn not_beyond_service_end() {
+    let map = night_bus(vec![Trip::new(1, vec![600, 900]).with_service(0)]);
+    let platforms = Platforms::from(Walking::from([(0, 0)]), Walking::from([(1, 0)]));
+    let date = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+    let mut searcher = Searcher::new(&map, platforms, Options::default()).with_date(date);
+    assert!(searcher.run(85800).is_empty());
+}
+
+#[test]
+fn arrive_by_previous_day() {
+    let map = night_bus(vec![Trip::new(1, vec![85800, 86100]).with_service(0)]);
+    let platforms = Platforms::from(Walking::from([(0, 0)]), Walking::from([(1, 0)]));
+    let date = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+    let mut searcher = ReverseSearcher::new(&map, platforms).with_date(date);
+    let departures: Vec<_> = searcher.run(300).iter().map(|p| p.departure).collect();
+    assert_eq!(vec![-600], departures);
+}
+
+#[test]
+fn absolute_times() {
+    let date = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+    let path = Path::new(vec![], 85800, 87300).with_date(date);
+    let departure = date.and_hms_opt(23, 50, 0).unwrap();
+    let arrival = date.succ_opt().unwrap().and_hms_opt(0, 15, 0).unwrap();
+    assert_eq!(
+        Some(departure),
+        path.departure_time().map(|t| t.naive_local())
+    );
+    assert_eq!(Some(arrival), path.arrival_time().map(|t| t.naive_local()));
+}
+
+#[test]
+fn live_delay() {
+    let mut map = change_at_platform();
+    let platforms = || Platforms::from(Walking::from([(0, 0)]), Walking::from([(2, 0)]));
+    map.delay_trip(1, &[Delay::new(1, 5)]);
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![50], arrivals(searcher.run(0)));
+
+    map.delay_trip(1, &[]);
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![30], arrivals(searcher.run(0)));
+}
+
+#[test]
+fn cancelled_trip() {
+    let mut map = change_at_platform();
+    let platforms = || Platforms::from(Walking::from([(0, 0)]), Walking::from([(2, 0)]));
+    assert!(map.cancel_trip(2, true));
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![50], arrivals(searcher.run(0)));
+
+    map.cancel_trip(2, false);
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![30], arrivals(searcher.run(0)));
+}
+
+#[test]
+fn closed_platform() {
+    let mut map = walk_or_ride();
+    let platforms = || Platforms::from(Walking::from([(0, 5)]), Walking::from([(2, 5)]));
+    map.platforms[1].close_fully();
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![105], arrivals(searcher.run(0)));
+
+    map.platforms[1].reopen();
+    map.platforms[3].close(30..60);
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![105], arrivals(searcher.run(0)));
+
+    let mut searcher = ReverseSearcher::new(&map, platforms());
+    assert_eq!(0, searcher.run(60).len());
+}
+
+#[test]
+fn frequent_trips() {
+    let map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0]),
+        ],
+        vec![Route::new(
+            false,
+            vec![0, 1],
+            vec![Trip::new(1, vec![0, 100]).with_frequencies(vec![Frequency::new(3600, 7200, 600)])],
+        )],
+        vec![vec![]; 2],
+    );
+    let platforms = || Platforms::from(Walking::from([(0, 0)]), Walking::from([(1, 0)]));
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![4300], arrivals(searcher.run(3700)));
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert!(searcher.run(6700).is_empty());
+
+    let mut searcher = ReverseSearcher::new(&map, platforms());
+    let departures: Vec<_> = searcher.run(4299).iter().map(|p| p.departure).collect();
+    assert_eq!(vec![3600], departures);
+}
+
+#[test]
+fn closed_when_trip_departs() {
+    let mut map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0]),
+            Platform::new(Point::new(0., 3.), vec![0]),
+        ],
+        vec![Route::new(
+            false,
+            vec![0, 1, 2],
+            vec![
+                Trip::new(1, vec![50, 60, 70]),
+                Trip::new(2, vec![100, 110, 120]),
+            ],
+        )],
+        vec![vec![]; 3],
+    );
+    let platforms = || Platforms::from(Walking::from([(0, 5)]), Walking::from([(2, 5)]));
+    // The rider is on the platform before it closes, the first trip leaves while it is closed
+    map.platforms[0].close(40..60);
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![125], arrivals(searcher.run(0)));
+    let mut searcher = McSearcher::new(&map, platforms(), Criteria::default());
+    assert_eq!(vec![125], arrivals(searcher.run(0)));
+
+    map.platforms[0].close(90..110);
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert!(searcher.run(0).is_empty());
+    let mut searcher = McSearcher::new(&map, platforms(), Criteria::default());
+    assert!(searcher.run(0).is_empty());
+
+    map.platforms[0].reopen();
+    map.platforms[2].close(115..125);
+    let mut searcher = ReverseSearcher::new(&map, platforms());
+    let departures: Vec<_> = searcher.run(130).iter().map(|p| p.departure).collect();
+    assert_eq!(vec![45], departures);
+}
+
+#[test]
+fn closed_simultaneous_departures() {
+    let mut map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0]),
+            Platform::new(Point::new(0., 3.), vec![0]),
+        ],
+        vec![Route::new(
+            false,
+            vec![0, 1, 2],
+            vec![
+                Trip::new(1, vec![50, 60, 70]),
+                Trip::new(2, vec![100, 110, 130]),
+                Trip::new(3, vec![100, 115, 130]),
+                Trip::new(4, vec![150, 160, 170]),
+            ],
+        )],
+        vec![vec![]; 3],
+    );
+    let platforms = || Platforms::from(Walking::from([(0, 5)]), Walking::from([(2, 5)]));
+    map.platforms[0].close(90..110);
+    let mut searcher = Searcher::new(&map, platforms(), Options::default());
+    assert_eq!(vec![175], arrivals(searcher.run(60)));
+    let mut searcher = McSearcher::new(&map, platforms(), Criteria::default());
+    assert_eq!(vec![175], arrivals(searcher.run(60)));
+
+    map.platforms[0].reopen();
+    map.platforms[2].close(125..135);
+    let mut searcher = ReverseSearcher::new(&map, platforms());
+    let departures: Vec<_> = searcher.run(140).iter().map(|p| p.departure).collect();
+    assert_eq!(vec![45], departures);
+}
+
+#[test]
+fn profile_frequent_trips() {
+    let map = PublicTransport::new(
+        vec![
+            Platform::new(Point::new(0., 1.), vec![0]),
+            Platform::new(Point::new(0., 2.), vec![0]),
+        ],
+        vec![Route::new(
+            false,
+            vec![0, 1],
+            vec![Trip::new(1, vec![0, 100]).with_frequencies(vec![Frequency::new(3600, 7200, 600)])],
+        )],
+        vec![vec![]; 2],
+    );
+    let platforms = Platforms::from(Walking::from([(0, 60)]), Walking::from([(1, 0)]));
+    let mut searcher = Searcher::new(&map, platforms, Options::default());
+    let departures: Vec<_> = searcher
+        .profile(3000..4800)
+        .iter()
+        .map(|p| p.departure)
+        .collect();
+    assert_eq!(vec![4740, 4140, 3540], departures);
+}